@@ -0,0 +1,66 @@
+#![feature(test)]
+extern crate test;
+
+use quadrax::cpu::storage::{Storage, codec::PodCodec, snapshot::ReadSnapshot};
+use test::Bencher;
+
+const ENTRIES: u32 = 10_000;
+/// Total reads per iteration, split evenly between the reading threads.
+const READS: u32 = 40_000;
+
+fn populated() -> (tokio::runtime::Runtime, Storage<u32, [f32; 4]>) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let storage = Storage::in_memory();
+    runtime.block_on(storage.insert_batch((0..ENTRIES).map(|i| (i, [i as f32; 4])).collect()));
+    (runtime, storage)
+}
+
+fn read_in_parallel(
+    snapshot: &ReadSnapshot<u32, [f32; 4], PodCodec, PodCodec>,
+    threads: u32,
+) -> f32 {
+    std::thread::scope(|scope| {
+        let readers = (0..threads)
+            .map(|t| {
+                scope.spawn(move || {
+                    (t * READS / threads..(t + 1) * READS / threads)
+                        .map(|i| snapshot.get(&((i * 7919) % ENTRIES)).unwrap().unwrap()[0])
+                        .sum::<f32>()
+                })
+            })
+            .collect::<Vec<_>>();
+        readers.into_iter().map(|r| r.join().unwrap()).sum()
+    })
+}
+
+/// Reads through the worker, for comparison with the snapshot benchmarks.
+#[bench]
+fn worker_get_1_thread(b: &mut Bencher) {
+    let (runtime, storage) = populated();
+    b.iter(|| {
+        runtime.block_on(async {
+            let mut sum = 0.0;
+            for i in 0..READS {
+                sum += storage.get((i * 7919) % ENTRIES).await.unwrap()[0];
+            }
+            sum
+        })
+    });
+}
+
+macro_rules! snapshot_bench {
+    ($($name:ident: $threads:expr),* $(,)?) => {$(
+        #[bench]
+        fn $name(b: &mut Bencher) {
+            let (runtime, storage) = populated();
+            let snapshot = runtime.block_on(storage.read_snapshot()).unwrap();
+            b.iter(|| read_in_parallel(&snapshot, $threads));
+        }
+    )*};
+}
+snapshot_bench!(
+    snapshot_get_1_thread: 1,
+    snapshot_get_2_threads: 2,
+    snapshot_get_4_threads: 4,
+    snapshot_get_8_threads: 8,
+);
@@ -65,24 +65,25 @@ impl<const NX: usize, const NY: usize> Matrix<NX, NY> {
             .sum()
     }
     pub fn transpose(&self) -> Matrix<NY, NX> {
-        let mut data = [[0.0_f32; NY]; NX];
-        for i in 0..NY {
-            for j in 0..NX {
-                data[j][i] = self.inner[i][j]; // swap rows and columns
-            }
-        }
+        let data = std::array::from_fn(|j| std::array::from_fn(|i| self.inner[i][j])); // swap rows and columns
         Matrix::new(data)
     }
     pub fn row(&self, i: usize) -> &Vector<NX> {
         &self.inner[i]
     }
 }
+impl<const N: usize> Matrix<N, N> {
+    pub fn identity() -> Self {
+        Self::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| if i == j { 1.0 } else { 0.0 })
+        }))
+    }
+}
 impl<const NX: usize, const NY: usize, const NZ: usize> BitOr<Matrix<NZ, NX>> for Matrix<NX, NY> {
     type Output = Matrix<NZ, NY>;
     fn bitor(self, rhs: Matrix<NZ, NX>) -> Self::Output {
         let rhs_t = rhs.transpose();
-        let inner =
-            std::array::from_fn(|i| std::array::from_fn(|j| self.row(i).dot(&rhs_t.row(j))));
+        let inner = std::array::from_fn(|i| std::array::from_fn(|j| self.row(i).dot(rhs_t.row(j))));
         Matrix {
             inner: inner.map(Vector::new),
         }
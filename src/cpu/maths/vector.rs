@@ -40,8 +40,7 @@ impl_elementwise_ops!(Add => add, Sub => sub, Mul => mul, Div => div);
 impl<const N: usize> Index<usize> for Vector<N> {
     type Output = f32;
     fn index(&self, index: usize) -> &Self::Output {
-        let val = &self.inner[index];
-        &val
+        &self.inner[index]
     }
 }
 impl<const N: usize> Vector<N> {
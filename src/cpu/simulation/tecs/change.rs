@@ -0,0 +1,250 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    cell::Cell,
+    collections::{HashMap, hash_map},
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use hecs::QueryBorrow;
+
+use crate::cpu::simulation::tecs::{EntityID, World, fault};
+
+thread_local! {
+    /// Change tick at the end of the last run of the system running on this thread.
+    static LAST_RUN: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Runs `f` as a system that last ran at change tick `last_run`, so that change filters only
+/// see what happened since.
+pub(crate) fn since<R>(last_run: u64, f: impl FnOnce() -> R) -> R {
+    fault::scoped(&LAST_RUN, last_run, f)
+}
+
+struct Entry<T> {
+    value: T,
+    added: u64,
+    changed: u64,
+    seen: u64,
+}
+
+/// Snapshot of every `T` as of the last sync point, with the change tick of its changes.
+struct Tracker<T> {
+    entries: HashMap<EntityID, Entry<T>>,
+    /// Entities that lost their `T`, with the change tick they were noticed at.
+    removed: Vec<(u64, EntityID)>,
+    /// Whether a filter has used `T`, which makes it worth comparing within ticks.
+    filtered: AtomicBool,
+}
+impl<T> Tracker<T> {
+    fn note_use(&self) {
+        self.filtered.store(true, Ordering::Relaxed);
+    }
+}
+
+trait AnyTracker: Send + Sync {
+    fn detect(&mut self, world: &hecs::World, tick: u64);
+    fn is_filtered(&self) -> bool;
+    fn expire(&mut self, before: u64);
+    fn as_any(&self) -> &dyn Any;
+}
+impl<T: hecs::Component + Clone + PartialEq> AnyTracker for Tracker<T> {
+    fn detect(&mut self, world: &hecs::World, tick: u64) {
+        for (entity, value) in world.query::<(EntityID, &T)>().iter() {
+            match self.entries.entry(entity) {
+                hash_map::Entry::Occupied(mut entry) => {
+                    let entry = entry.get_mut();
+                    if entry.value != *value {
+                        entry.value = value.clone();
+                        entry.changed = tick;
+                    }
+                    entry.seen = tick;
+                }
+                hash_map::Entry::Vacant(entry) => {
+                    entry.insert(Entry {
+                        value: value.clone(),
+                        added: tick,
+                        changed: tick,
+                        seen: tick,
+                    });
+                }
+            }
+        }
+        let start = self.removed.len();
+        self.entries.retain(|&entity, entry| {
+            if entry.seen != tick {
+                self.removed.push((tick, entity));
+            }
+            entry.seen == tick
+        });
+        self.removed[start..].sort_by_key(|(_, entity)| entity.to_bits());
+    }
+    fn is_filtered(&self) -> bool {
+        self.filtered.load(Ordering::Relaxed)
+    }
+    fn expire(&mut self, before: u64) {
+        self.removed.retain(|(tick, _)| *tick > before);
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Change trackers of a world, one per tracked component type. The change tick advances at
+/// every sync point, where the components that may have changed are compared with their
+/// previous values.
+#[derive(Default)]
+pub(crate) struct ChangeTrackers {
+    inner: HashMap<TypeId, Box<dyn AnyTracker>>,
+    tick: u64,
+    /// Change tick at the start of the current and previous world ticks.
+    tick_starts: [u64; 2],
+}
+impl ChangeTrackers {
+    pub(crate) fn add<T: hecs::Component + Clone + PartialEq>(&mut self) {
+        self.inner.entry(TypeId::of::<T>()).or_insert_with(|| {
+            Box::new(Tracker::<T> {
+                entries: HashMap::new(),
+                removed: Vec::new(),
+                filtered: AtomicBool::new(false),
+            })
+        });
+    }
+    fn get<T: 'static>(&self) -> &Tracker<T> {
+        self.inner
+            .get(&TypeId::of::<T>())
+            .and_then(|tracker| tracker.as_any().downcast_ref())
+            .unwrap_or_else(|| panic!("Component {} is not tracked.", type_name::<T>()))
+    }
+    pub(crate) fn tick(&self) -> u64 {
+        self.tick
+    }
+    /// Stamps the changes since the last sync point with a new change tick, comparing only the
+    /// components some filter has used and that `written` says may have been written since.
+    pub(crate) fn detect(&mut self, world: &hecs::World, written: impl Fn(TypeId) -> bool) {
+        self.tick += 1;
+        for (&type_id, tracker) in &mut self.inner {
+            if tracker.is_filtered() && written(type_id) {
+                tracker.detect(world, self.tick);
+            }
+        }
+    }
+    /// Compares every tracked component to catch the changes made since the last sync point,
+    /// including those between world ticks, then drops the removals noticed before the
+    /// previous world tick.
+    pub(crate) fn start_tick(&mut self, world: &hecs::World) {
+        self.tick += 1;
+        for tracker in self.inner.values_mut() {
+            tracker.detect(world, self.tick);
+        }
+        let expired = self.tick_starts[0];
+        self.tick_starts = [self.tick_starts[1], self.tick];
+        for tracker in self.inner.values_mut() {
+            tracker.expire(expired);
+        }
+    }
+}
+
+/// Conditions on the change ticks of an entity's components, for `World::query_filtered`.
+pub trait Filter {
+    fn matches(world: &World, entity: EntityID, last_run: u64) -> bool;
+    /// Marks the components the filter reads, so that their changes are also detected within
+    /// ticks.
+    fn note_use(world: &World);
+}
+/// Entities whose `T` was added since the system last ran.
+pub struct Added<T>(PhantomData<fn() -> T>);
+impl<T: 'static> Filter for Added<T> {
+    fn note_use(world: &World) {
+        world.changes.get::<T>().note_use();
+    }
+    fn matches(world: &World, entity: EntityID, last_run: u64) -> bool {
+        let tracker = world.changes.get::<T>();
+        tracker
+            .entries
+            .get(&entity)
+            .is_some_and(|entry| entry.added > last_run)
+    }
+}
+/// Entities whose `T` was added or changed since the system last ran.
+pub struct Changed<T>(PhantomData<fn() -> T>);
+impl<T: 'static> Filter for Changed<T> {
+    fn note_use(world: &World) {
+        world.changes.get::<T>().note_use();
+    }
+    fn matches(world: &World, entity: EntityID, last_run: u64) -> bool {
+        let tracker = world.changes.get::<T>();
+        tracker
+            .entries
+            .get(&entity)
+            .is_some_and(|entry| entry.changed > last_run)
+    }
+}
+macro_rules! impl_filter {
+    ($($name:ident),*) => {
+        impl<$($name: Filter),*> Filter for ($($name,)*) {
+            fn matches(_world: &World, _entity: EntityID, _last_run: u64) -> bool {
+                true $(&& $name::matches(_world, _entity, _last_run))*
+            }
+            fn note_use(_world: &World) {
+                $($name::note_use(_world);)*
+            }
+        }
+    };
+}
+impl_filter!();
+impl_filter!(A);
+impl_filter!(A, B);
+impl_filter!(A, B, C);
+impl_filter!(A, B, C, D);
+
+/// A query over the entities passing the filter `F`, returned by `World::query_filtered`.
+pub struct Filtered<'w, Q: hecs::Query, F> {
+    world: &'w World,
+    borrow: QueryBorrow<'w, (EntityID, Q)>,
+    last_run: u64,
+    types: PhantomData<fn() -> F>,
+}
+impl<'w, Q: hecs::Query, F: Filter> Filtered<'w, Q, F> {
+    pub(crate) fn new(world: &'w World) -> Self {
+        F::note_use(world);
+        Self {
+            world,
+            borrow: world.hecs_world.query(),
+            last_run: LAST_RUN.get(),
+            types: PhantomData,
+        }
+    }
+    pub fn iter(&mut self) -> impl Iterator<Item = Q::Item<'_>> {
+        let (world, last_run) = (self.world, self.last_run);
+        self.borrow
+            .iter()
+            .filter(move |(entity, _)| F::matches(world, *entity, last_run))
+            .map(|(_, item)| item)
+    }
+}
+
+/// Entities that lost their `T`, by removal or despawning, since the system last ran. Only
+/// removals from this world tick and the previous one are kept.
+pub struct RemovedComponents<'w, T> {
+    removed: &'w [(u64, EntityID)],
+    last_run: u64,
+    types: PhantomData<fn() -> T>,
+}
+impl<'w, T: 'static> RemovedComponents<'w, T> {
+    pub(crate) fn new(world: &'w World) -> Self {
+        let tracker = world.changes.get::<T>();
+        tracker.note_use();
+        Self {
+            removed: &tracker.removed,
+            last_run: LAST_RUN.get(),
+            types: PhantomData,
+        }
+    }
+    pub fn iter(&self) -> impl Iterator<Item = EntityID> + '_ {
+        self.removed
+            .iter()
+            .filter(|(tick, _)| *tick > self.last_run)
+            .map(|(_, entity)| *entity)
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use tokio::sync::oneshot;
+
+use crate::cpu::simulation::tecs::{World, fault::SystemPanic};
+
+/// Simulation time as seen by systems. During a tick, `tick` and `elapsed` count the ticks
+/// completed before it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Time {
+    tick: u64,
+    dt: f64,
+    elapsed: f64,
+}
+impl Time {
+    pub(crate) fn new(dt: f64) -> Self {
+        Self {
+            tick: 0,
+            dt,
+            elapsed: 0.0,
+        }
+    }
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+    /// Fixed timestep in simulated seconds.
+    pub fn dt(&self) -> f64 {
+        self.dt
+    }
+    /// Simulated seconds since the start.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+    pub(crate) fn advance(&mut self) {
+        self.tick += 1;
+        self.elapsed = self.tick as f64 * self.dt;
+    }
+}
+
+/// How fast a running simulation ticks.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Pacing {
+    /// As fast as possible.
+    #[default]
+    Unpaced,
+    /// One tick every `dt / time_scale` wall-clock seconds.
+    RealTime { time_scale: f64 },
+}
+
+/// What the simulation thread does between messages.
+#[derive(Default)]
+pub(crate) enum Mode {
+    /// Only ticks when asked to.
+    #[default]
+    Idle,
+    Continuous,
+    For(u64),
+    Until(Box<dyn FnMut(&World) -> bool + Send>),
+}
+
+/// Run-loop state of the simulation thread.
+#[derive(Default)]
+pub(crate) struct Clock {
+    mode: Mode,
+    pub(crate) pacing: Pacing,
+    pub(crate) paused: bool,
+    next_tick: Option<Instant>,
+}
+impl Clock {
+    /// Switches to `mode`, resuming if paused.
+    pub(crate) fn start(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.paused = false;
+    }
+    pub(crate) fn stop(&mut self) {
+        self.mode = Mode::Idle;
+    }
+    pub(crate) fn is_running(&self) -> bool {
+        !self.paused && !matches!(self.mode, Mode::Idle)
+    }
+    /// How long to wait for messages before the next tick is due, or `None` to block until one
+    /// arrives.
+    pub(crate) fn wait(&mut self, dt: f64) -> Option<Duration> {
+        if !self.is_running() {
+            self.next_tick = None;
+            return None;
+        }
+        match self.pacing {
+            Pacing::Unpaced => Some(Duration::ZERO),
+            Pacing::RealTime { time_scale } => {
+                let now = Instant::now();
+                let next = *self.next_tick.get_or_insert(now);
+                // Start over rather than rushing through missed ticks after a stall.
+                let period = Duration::from_secs_f64(dt / time_scale);
+                if now > next + period {
+                    self.next_tick = Some(now);
+                }
+                Some(self.next_tick.unwrap().saturating_duration_since(now))
+            }
+        }
+    }
+    /// Records a tick of the current mode, returning to `Idle` once it is done.
+    pub(crate) fn ticked(&mut self, world: &World) {
+        if let (Pacing::RealTime { time_scale }, Some(next)) = (self.pacing, &mut self.next_tick) {
+            *next += Duration::from_secs_f64(world.time().dt() / time_scale);
+        }
+        let done = match &mut self.mode {
+            Mode::Idle | Mode::Continuous => false,
+            Mode::For(remaining) => {
+                *remaining -= 1;
+                *remaining == 0
+            }
+            Mode::Until(predicate) => predicate(world),
+        };
+        if done {
+            self.mode = Mode::Idle;
+        }
+    }
+}
+
+/// Completion of queued ticks, returned by `TECS::tick`. Resolves to the number of ticks run
+/// since the start, either through `wait` or as a future, or to the first system panic among the
+/// ticks.
+pub struct TickHandle {
+    receiver: oneshot::Receiver<Result<u64, SystemPanic>>,
+}
+impl TickHandle {
+    pub(crate) fn new(receiver: oneshot::Receiver<Result<u64, SystemPanic>>) -> Self {
+        Self { receiver }
+    }
+    /// Blocks until the ticks have run. Must not be called from an async context.
+    pub fn wait(self) -> Result<u64> {
+        Self::resolve(self.receiver.blocking_recv())
+    }
+    fn resolve(
+        received: Result<Result<u64, SystemPanic>, oneshot::error::RecvError>,
+    ) -> Result<u64> {
+        received
+            .map_err(|_| anyhow!("Simulation thread stopped before the tick completed."))?
+            .map_err(Into::into)
+    }
+}
+impl Future for TickHandle {
+    type Output = Result<u64>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<u64>> {
+        Pin::new(&mut self.receiver).poll(cx).map(Self::resolve)
+    }
+}
@@ -0,0 +1,93 @@
+use std::{cell::Cell, sync::Mutex};
+
+use crate::cpu::simulation::tecs::{EntityID, World, fault, hierarchy};
+
+type Command = Box<dyn FnOnce(&mut hecs::World) + Send>;
+
+thread_local! {
+    /// Schedule position of the system running on this thread.
+    static SYSTEM: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+/// Runs `f` as the system at `position` in the schedule, so that the commands recorded by a
+/// batch of parallel systems are applied in the same order every time.
+pub(crate) fn as_system<R>(position: usize, f: impl FnOnce() -> R) -> R {
+    fault::scoped(&SYSTEM, position, f)
+}
+
+/// Structural changes recorded while the world is borrowed, applied at the next sync point.
+#[derive(Default)]
+pub(crate) struct CommandQueue {
+    inner: Mutex<Vec<(usize, Command)>>,
+}
+impl CommandQueue {
+    fn push(&self, command: Command) {
+        let position = SYSTEM.get();
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((position, command));
+    }
+    /// Applies every recorded command, system by system. Commands on entities that no longer
+    /// exist are skipped.
+    /// Applies every recorded command, returning whether there were any.
+    pub(crate) fn apply(&mut self, world: &mut hecs::World) -> bool {
+        let inner = self.inner.get_mut().unwrap_or_else(|e| e.into_inner());
+        if inner.is_empty() {
+            return false;
+        }
+        let mut commands = std::mem::take(inner);
+        commands.sort_by_key(|(position, _)| *position);
+        for (_, command) in commands {
+            command(world);
+        }
+        true
+    }
+}
+
+/// Records spawns, despawns and component changes for the next sync point, which comes after
+/// the running batch of systems, or after the tick for commands recorded outside systems.
+pub struct Commands<'w> {
+    world: &'w World,
+}
+impl<'w> Commands<'w> {
+    pub(crate) fn new(world: &'w World) -> Self {
+        Self { world }
+    }
+    /// Spawns an entity at the next sync point, returning its reserved ID straight away.
+    pub fn spawn(&self, components: impl hecs::DynamicBundle + Send + 'static) -> EntityID {
+        let entity = self.world.hecs_world.reserve_entity();
+        self.insert(entity, components);
+        entity
+    }
+    /// Despawns an entity, leaving its children without a parent.
+    pub fn despawn(&self, entity: EntityID) {
+        self.world.commands.push(Box::new(move |world| {
+            let _ = hierarchy::despawn(world, entity, false);
+        }));
+    }
+    /// Despawns an entity and all its descendants.
+    pub fn despawn_recursive(&self, entity: EntityID) {
+        self.world.commands.push(Box::new(move |world| {
+            let _ = hierarchy::despawn(world, entity, true);
+        }));
+    }
+    /// Hangs `child` from `parent`, unless either is gone by then or it would make a cycle.
+    pub fn set_parent(&self, child: EntityID, parent: EntityID) {
+        self.world.commands.push(Box::new(move |world| {
+            let _ = hierarchy::set_parent(world, child, parent);
+        }));
+    }
+    /// Adds or replaces components of `entity`.
+    pub fn insert(&self, entity: EntityID, components: impl hecs::DynamicBundle + Send + 'static) {
+        self.world.commands.push(Box::new(move |world| {
+            let _ = world.insert(entity, components);
+        }));
+    }
+    /// Removes the components `T` of `entity`, if it has all of them.
+    pub fn remove<T: hecs::Bundle + 'static>(&self, entity: EntityID) {
+        self.world.commands.push(Box::new(move |world| {
+            let _ = world.remove::<T>(entity);
+        }));
+    }
+}
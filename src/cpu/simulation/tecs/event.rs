@@ -0,0 +1,110 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    collections::{HashMap, VecDeque},
+    marker::PhantomData,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::cpu::simulation::tecs::World;
+
+/// Events of one type, each kept for the tick it was sent in and the next one.
+pub(crate) struct EventQueue<E> {
+    events: VecDeque<(u64, E)>,
+    next_id: u64,
+    /// Id of the first event sent during the current tick.
+    this_tick: u64,
+}
+impl<E> EventQueue<E> {
+    fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            next_id: 0,
+            this_tick: 0,
+        }
+    }
+}
+
+trait AnyQueue: Send + Sync {
+    fn update(&self);
+    fn as_any(&self) -> &dyn Any;
+}
+impl<E: Send + Sync + 'static> AnyQueue for Mutex<EventQueue<E>> {
+    /// Drops the events sent before the tick that just ended.
+    fn update(&self) {
+        let mut queue = self.lock().unwrap_or_else(|e| e.into_inner());
+        let expired = queue.this_tick;
+        while queue.events.front().is_some_and(|(id, _)| *id < expired) {
+            queue.events.pop_front();
+        }
+        queue.this_tick = queue.next_id;
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Event queues of a world, one per registered event type.
+#[derive(Default)]
+pub(crate) struct EventQueues {
+    inner: HashMap<TypeId, Box<dyn AnyQueue>>,
+}
+impl EventQueues {
+    pub(crate) fn add<E: Send + Sync + 'static>(&mut self) {
+        self.inner
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(Mutex::new(EventQueue::<E>::new())));
+    }
+    pub(crate) fn get<E: Send + Sync + 'static>(&self) -> MutexGuard<'_, EventQueue<E>> {
+        self.inner
+            .get(&TypeId::of::<E>())
+            .and_then(|queue| queue.as_any().downcast_ref::<Mutex<EventQueue<E>>>())
+            .unwrap_or_else(|| panic!("Event type {} is not registered.", type_name::<E>()))
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+    pub(crate) fn send<E: Send + Sync + 'static>(&self, event: E) {
+        let mut queue = self.get::<E>();
+        let id = queue.next_id;
+        queue.events.push_back((id, event));
+        queue.next_id += 1;
+    }
+    pub(crate) fn drain<E: Send + Sync + 'static>(&self) -> Vec<E> {
+        self.get::<E>().events.drain(..).map(|(_, e)| e).collect()
+    }
+    pub(crate) fn update(&self) {
+        for queue in self.inner.values() {
+            queue.update();
+        }
+    }
+}
+
+/// Reads the events of type `E` a system hasn't seen yet. Each system keeps its own reader.
+pub struct EventReader<E> {
+    cursor: u64,
+    types: PhantomData<fn() -> E>,
+}
+impl<E> Default for EventReader<E> {
+    fn default() -> Self {
+        Self {
+            cursor: 0,
+            types: PhantomData,
+        }
+    }
+}
+impl<E: Clone + Send + Sync + 'static> EventReader<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Events sent since the last read, skipping any that expired in between.
+    pub fn read(&mut self, world: &World) -> Vec<E> {
+        let queue = world.events.get::<E>();
+        let unread = queue
+            .events
+            .iter()
+            .filter(|(id, _)| *id >= self.cursor)
+            .map(|(_, e)| e.clone())
+            .collect();
+        self.cursor = queue.next_id;
+        unread
+    }
+}
@@ -0,0 +1,56 @@
+use std::{any::Any, cell::Cell, thread::LocalKey};
+
+/// What the simulation does when a system panics.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OnPanic {
+    /// Abandon the tick and refuse further ticks, keeping the world for inspection.
+    #[default]
+    Halt,
+    /// Disable the system and carry on without it.
+    Disable,
+}
+
+/// A panic caught in a system, reported by the tick it happened in.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SystemPanic {
+    pub system: String,
+    pub tick: u64,
+    pub message: String,
+}
+impl std::fmt::Display for SystemPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "System {} panicked at tick {}: {}",
+            self.system, self.tick, self.message
+        )
+    }
+}
+impl std::error::Error for SystemPanic {}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_else(|| "unknown panic payload".to_string()),
+    }
+}
+
+/// Sets the thread-local `key` to `value` while `f` runs, putting back the outer value
+/// afterwards, even when `f` panics.
+pub(crate) fn scoped<T: Copy + 'static, R>(
+    key: &'static LocalKey<Cell<T>>,
+    value: T,
+    f: impl FnOnce() -> R,
+) -> R {
+    struct Restore<T: Copy + 'static>(&'static LocalKey<Cell<T>>, T);
+    impl<T: Copy + 'static> Drop for Restore<T> {
+        fn drop(&mut self) {
+            self.0.set(self.1);
+        }
+    }
+    let _restore = Restore(key, key.replace(value));
+    f()
+}
@@ -0,0 +1,24 @@
+use crate::cpu::simulation::tecs::EntityID;
+
+/// Tuples of components that can be cloned out of an entity, for reading it from outside the
+/// simulation thread.
+pub trait Fetch: Sized {
+    fn fetch(world: &hecs::World, entity: EntityID) -> Option<Self>;
+}
+macro_rules! impl_fetch {
+    ($($name:ident),*) => {
+        impl<$($name: hecs::Component + Clone),*> Fetch for ($($name,)*) {
+            fn fetch(world: &hecs::World, entity: EntityID) -> Option<Self> {
+                Some(($($name::clone(&*world.get::<&$name>(entity).ok()?),)*))
+            }
+        }
+    };
+}
+impl_fetch!(A);
+impl_fetch!(A, B);
+impl_fetch!(A, B, C);
+impl_fetch!(A, B, C, D);
+impl_fetch!(A, B, C, D, E);
+impl_fetch!(A, B, C, D, E, F);
+impl_fetch!(A, B, C, D, E, F, G);
+impl_fetch!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,178 @@
+use anyhow::ensure;
+
+use crate::cpu::{
+    maths::matrix::Matrix,
+    simulation::tecs::{
+        EntityID, World,
+        schedule::Stage,
+        system::{Access, SystemSpec},
+    },
+};
+
+/// The entity an entity hangs from, set with `World::set_parent` along with the parent's
+/// `Children`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Parent(EntityID);
+impl Parent {
+    pub fn get(&self) -> EntityID {
+        self.0
+    }
+}
+
+/// The entities hanging from an entity, in the order they were attached.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Children(Vec<EntityID>);
+impl Children {
+    pub fn iter(&self) -> impl Iterator<Item = EntityID> + '_ {
+        self.0.iter().copied()
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Transform relative to the parent, or to the world for entities without one.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LocalTransform(pub Matrix<4, 4>);
+
+/// Transform relative to the world, computed by `propagate_transforms`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GlobalTransform(pub Matrix<4, 4>);
+
+/// Hangs `child` from `parent`, detaching it from its previous parent. Fails if either entity
+/// is missing or `parent` is `child` or one of its descendants.
+pub(crate) fn set_parent(
+    world: &mut hecs::World,
+    child: EntityID,
+    parent: EntityID,
+) -> anyhow::Result<()> {
+    ensure!(world.contains(child), "Entity {child:?} does not exist.");
+    ensure!(world.contains(parent), "Entity {parent:?} does not exist.");
+    ensure!(
+        parent != child && !ancestors(world, parent).contains(&child),
+        "Entity {child:?} cannot hang from its own descendant {parent:?}."
+    );
+    detach(world, child);
+    world.insert_one(child, Parent(parent))?;
+    let attached = match world.get::<&mut Children>(parent) {
+        Ok(mut children) => {
+            children.0.push(child);
+            true
+        }
+        Err(_) => false,
+    };
+    if !attached {
+        world.insert_one(parent, Children(vec![child]))?;
+    }
+    Ok(())
+}
+
+/// Takes `child` off its parent, if it has one.
+pub(crate) fn detach(world: &mut hecs::World, child: EntityID) {
+    let Ok(Parent(parent)) = world.remove_one::<Parent>(child) else {
+        return;
+    };
+    let orphaned = match world.get::<&mut Children>(parent) {
+        Ok(mut children) => {
+            children.0.retain(|&c| c != child);
+            children.is_empty()
+        }
+        Err(_) => false,
+    };
+    if orphaned {
+        let _ = world.remove_one::<Children>(parent);
+    }
+}
+
+/// Despawns `entity`, along with its descendants if `recursive`, or else leaving its children
+/// without a parent.
+pub(crate) fn despawn(
+    world: &mut hecs::World,
+    entity: EntityID,
+    recursive: bool,
+) -> Result<(), hecs::NoSuchEntity> {
+    if !world.contains(entity) {
+        return Err(hecs::NoSuchEntity);
+    }
+    detach(world, entity);
+    if recursive {
+        for descendant in descendants(world, entity) {
+            world.despawn(descendant)?;
+        }
+    } else if let Ok(children) = world.remove_one::<Children>(entity) {
+        for child in children.iter() {
+            let _ = world.remove_one::<Parent>(child);
+        }
+    }
+    world.despawn(entity)
+}
+
+/// Every entity below `entity`, depth first, each before its own children.
+pub(crate) fn descendants(world: &hecs::World, entity: EntityID) -> Vec<EntityID> {
+    let children = |entity| {
+        world
+            .get::<&Children>(entity)
+            .map_or_else(|_| Vec::new(), |children| children.0.clone())
+    };
+    let mut descendants = Vec::new();
+    let mut stack = children(entity);
+    stack.reverse();
+    while let Some(entity) = stack.pop() {
+        descendants.push(entity);
+        stack.extend(children(entity).into_iter().rev());
+    }
+    descendants
+}
+
+/// Every entity above `entity`, from its parent up to the root.
+pub(crate) fn ancestors(world: &hecs::World, entity: EntityID) -> Vec<EntityID> {
+    let mut ancestors = Vec::new();
+    let mut current = entity;
+    while let Ok(parent) = world.get::<&Parent>(current) {
+        current = parent.0;
+        ancestors.push(current);
+    }
+    ancestors
+}
+
+/// Sets the `GlobalTransform` of every entity with a `LocalTransform` to the product of the
+/// local transforms from its root down. Entities hanging from one without a `LocalTransform`
+/// are left alone, along with their descendants. Missing `GlobalTransform`s are added at the
+/// next sync point.
+pub fn propagate_transforms(world: &World) {
+    let hecs_world = &world.hecs_world;
+    let mut stack = hecs_world
+        .query::<(EntityID, &LocalTransform)>()
+        .without::<&Parent>()
+        .iter()
+        .map(|(entity, local)| (entity, local.0))
+        .collect::<Vec<_>>();
+    while let Some((entity, global)) = stack.pop() {
+        match hecs_world.get::<&mut GlobalTransform>(entity) {
+            Ok(mut transform) => transform.0 = global,
+            Err(_) => world.commands().insert(entity, (GlobalTransform(global),)),
+        }
+        if let Ok(children) = hecs_world.get::<&Children>(entity) {
+            for child in children.iter() {
+                if let Ok(local) = hecs_world.get::<&LocalTransform>(child) {
+                    stack.push((child, global | local.0));
+                }
+            }
+        }
+    }
+}
+
+/// `propagate_transforms` as a system in `Stage::PostUpdate`, named `propagate_transforms`.
+pub fn transform_propagation() -> SystemSpec {
+    let access = Access::default()
+        .read::<LocalTransform>()
+        .read::<Parent>()
+        .read::<Children>()
+        .write::<GlobalTransform>();
+    SystemSpec::shared(access, propagate_transforms)
+        .named("propagate_transforms")
+        .in_stage(Stage::PostUpdate)
+}
@@ -1,43 +1,138 @@
 use std::{
-    sync::mpsc::{self, Sender},
+    any::TypeId,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Sender, SyncSender},
+    },
     thread::JoinHandle,
 };
 
+use anyhow::anyhow;
 use hecs::{QueryBorrow, QueryMut};
 
+use crate::cpu::simulation::tecs::{
+    change::{ChangeTrackers, Filter, Filtered, RemovedComponents},
+    clock::{Pacing, TickHandle, Time},
+    command::{CommandQueue, Commands},
+    event::EventQueues,
+    fault::{OnPanic, SystemPanic, panic_message},
+    fetch::Fetch,
+    hierarchy::{Children, Parent},
+    resource::{Res, ResMut, Resources},
+    schedule::Schedule,
+    script::prefab::Prefabs,
+    system::SystemSpec,
+    worker::Worker,
+};
+
+pub mod change;
+pub mod clock;
+pub mod command;
+pub mod event;
+pub mod fault;
+pub mod fetch;
+pub mod hierarchy;
+pub mod resource;
+pub mod schedule;
+pub mod script;
+pub mod system;
+mod worker;
+
 pub type EntityID = hecs::Entity;
 pub trait System = FnMut(&mut World) + Send;
 pub trait Query = hecs::Query;
 
 pub struct World {
     hecs_world: hecs::World,
-    systems_bundles: Vec<Vec<Box<dyn System>>>,
+    resources: Resources,
+    events: EventQueues,
+    commands: CommandQueue,
+    changes: ChangeTrackers,
+    time: Time,
 }
 impl World {
-    fn new() -> Self {
+    fn new(dt: f64) -> Self {
         Self {
             hecs_world: hecs::World::new(),
-            systems_bundles: Vec::new(),
+            resources: Resources::default(),
+            events: EventQueues::default(),
+            commands: CommandQueue::default(),
+            changes: ChangeTrackers::default(),
+            time: Time::new(dt),
         }
     }
+    /// Runs a tick, which a panicking system under `OnPanic::Halt` abandons. The panics of
+    /// systems disabled instead are added to `disabled`.
+    fn tick(
+        &mut self,
+        schedule: &mut Schedule,
+        on_panic: OnPanic,
+        disabled: &mut Vec<SystemPanic>,
+    ) -> Result<(), SystemPanic> {
+        self.changes.start_tick(&self.hecs_world);
+        schedule.run(self, on_panic, disabled)?;
+        self.apply_commands();
+        self.events.update();
+        self.time.advance();
+        Ok(())
+    }
     pub fn spawn(&mut self, components: impl hecs::DynamicBundle) -> EntityID {
         self.hecs_world.spawn(components)
     }
+    /// Despawns `entity`, detaching it from its parent. Its children are left without one.
     pub fn despawn(&mut self, entity: EntityID) -> anyhow::Result<()> {
-        self.hecs_world.despawn(entity)?;
+        hierarchy::despawn(&mut self.hecs_world, entity, false)?;
         Ok(())
     }
-    fn add_systems(&mut self, systems: Vec<Box<dyn System>>) {
-        self.systems_bundles.push(systems);
+    /// Despawns `entity` and all its descendants.
+    pub fn despawn_recursive(&mut self, entity: EntityID) -> anyhow::Result<()> {
+        hierarchy::despawn(&mut self.hecs_world, entity, true)?;
+        Ok(())
     }
-    fn run_systems(&mut self) {
-        let mut bundles = std::mem::take(&mut self.systems_bundles);
-        for bundle in bundles.iter_mut() {
-            for system in bundle {
-                system(self)
-            }
-        }
-        self.systems_bundles = bundles;
+    /// Hangs `child` from `parent`, keeping the `Parent` and `Children` components of both
+    /// sides in step. Fails if either is missing or it would make a cycle.
+    pub fn set_parent(&mut self, child: EntityID, parent: EntityID) -> anyhow::Result<()> {
+        hierarchy::set_parent(&mut self.hecs_world, child, parent)
+    }
+    /// Makes `child` a root, if it has a parent.
+    pub fn remove_parent(&mut self, child: EntityID) {
+        hierarchy::detach(&mut self.hecs_world, child);
+    }
+    pub fn parent(&self, entity: EntityID) -> Option<EntityID> {
+        self.hecs_world.get::<&Parent>(entity).ok().map(|p| p.get())
+    }
+    pub fn children(&self, entity: EntityID) -> Vec<EntityID> {
+        self.hecs_world
+            .get::<&Children>(entity)
+            .map_or_else(|_| Vec::new(), |children| children.iter().collect())
+    }
+    /// Every entity below `entity`, depth first, each before its own children.
+    pub fn descendants(&self, entity: EntityID) -> Vec<EntityID> {
+        hierarchy::descendants(&self.hecs_world, entity)
+    }
+    /// Every entity above `entity`, from its parent up to the root.
+    pub fn ancestors(&self, entity: EntityID) -> Vec<EntityID> {
+        hierarchy::ancestors(&self.hecs_world, entity)
+    }
+    pub fn contains(&self, entity: EntityID) -> bool {
+        self.hecs_world.contains(entity)
+    }
+    /// Adds or replaces components of `entity`.
+    pub fn insert(
+        &mut self,
+        entity: EntityID,
+        components: impl hecs::DynamicBundle,
+    ) -> anyhow::Result<()> {
+        self.hecs_world.insert(entity, components)?;
+        Ok(())
+    }
+    /// Takes the components `T` off `entity`. Fails unless it has all of them.
+    pub fn remove<T: hecs::Bundle + 'static>(&mut self, entity: EntityID) -> anyhow::Result<T> {
+        Ok(self.hecs_world.remove::<T>(entity)?)
+    }
+    /// Clones the components `T` of `entity`, if it has all of them.
+    pub fn fetch<T: Fetch>(&self, entity: EntityID) -> Option<T> {
+        T::fetch(&self.hecs_world, entity)
     }
     pub fn query<T: hecs::Query>(&self) -> QueryBorrow<'_, T> {
         self.hecs_world.query::<T>()
@@ -45,10 +140,88 @@ impl World {
     pub fn query_mut<T: hecs::Query>(&mut self) -> QueryMut<'_, T> {
         self.hecs_world.query_mut::<T>()
     }
+    /// Queries the entities passing the change filter `F`, such as `Changed<T>` or a tuple of
+    /// filters, relative to the last run of the calling system. Outside systems, every change
+    /// since tracking began counts. The filtered components must be tracked.
+    pub fn query_filtered<Q: hecs::Query, F: Filter>(&self) -> Filtered<'_, Q, F> {
+        Filtered::new(self)
+    }
+    /// Entities that lost their `T` since the calling system last ran. Panics if `T` is not
+    /// tracked.
+    pub fn removed<T: 'static>(&self) -> RemovedComponents<'_, T> {
+        RemovedComponents::new(self)
+    }
+    /// Tracks changes to the component `T` for change filters. Changes are found by comparing
+    /// every `T` with a copy of its previous value, which scans all of them: once at the start
+    /// of each tick, and, once a filter has used `T`, after each batch of systems that may have
+    /// written it, by declaring it in their `Access`, running exclusively or sending commands.
+    /// Undeclared writes are only noticed at the next tick. Since values rather than writes are
+    /// compared, a change undone before the next comparison goes unseen, and a value that is
+    /// not equal to itself, such as a float holding NaN, counts as changed at every comparison.
+    /// Untracked components cost nothing.
+    pub fn track<T: hecs::Component + Clone + PartialEq>(&mut self) {
+        self.changes.add::<T>();
+    }
+    /// Stamps the changes to filtered components that `written` says may have been written
+    /// since the last sync point.
+    pub(crate) fn detect_changes(&mut self, written: impl Fn(TypeId) -> bool) {
+        self.changes.detect(&self.hecs_world, written);
+    }
+    /// Records structural changes to apply once the world is no longer borrowed.
+    pub fn commands(&self) -> Commands<'_> {
+        Commands::new(self)
+    }
+    /// Applies the recorded commands, returning whether there were any.
+    pub(crate) fn apply_commands(&mut self) -> bool {
+        self.commands.apply(&mut self.hecs_world)
+    }
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+    /// Stores `value` as the world's `T`, returning the previous one.
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.resources.insert(value)
+    }
+    pub fn remove_resource<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.resources.remove()
+    }
+    /// Borrows the world's `T`. Panics if it is mutably borrowed, so systems running in
+    /// parallel should declare it with `Access::read`.
+    pub fn resource<T: 'static>(&self) -> Option<Res<'_, T>> {
+        self.resources.get()
+    }
+    /// Mutably borrows the world's `T`. Panics if it is already borrowed, so systems running
+    /// in parallel should declare it with `Access::write`.
+    pub fn resource_mut<T: 'static>(&self) -> Option<ResMut<'_, T>> {
+        self.resources.get_mut()
+    }
+    /// Registers the event type `E`. Events live for the tick they are sent in and the next.
+    pub fn add_event<E: Send + Sync + 'static>(&mut self) {
+        self.events.add::<E>();
+    }
+    /// Queues `event` for the `EventReader`s of `E`. Panics if `E` is not registered. Systems
+    /// running in parallel should declare it with `Access::send_event`.
+    pub fn send_event<E: Send + Sync + 'static>(&self, event: E) {
+        self.events.send(event);
+    }
+    /// Takes every buffered event of type `E`, so that readers no longer see them.
+    pub fn drain_events<E: Send + Sync + 'static>(&mut self) -> Vec<E> {
+        self.events.drain()
+    }
 }
 
 enum Message {
-    Tick,
+    Tick {
+        ticks: u64,
+        done: tokio::sync::oneshot::Sender<Result<u64, SystemPanic>>,
+    },
+    Run,
+    RunFor(u64),
+    RunUntil(Box<dyn FnMut(&World) -> bool + Send>),
+    Pause,
+    Resume,
+    Pacing(Pacing),
+    Apply(Box<dyn FnOnce(&mut World) + Send>),
     Create {
         spawn_fn: Box<dyn FnOnce(&mut World) -> EntityID + Send>,
         response: std::sync::mpsc::Sender<EntityID>,
@@ -57,68 +230,269 @@ enum Message {
         entity: EntityID,
     },
     Systems {
-        systems: Vec<Box<dyn System>>,
+        systems: Vec<SystemSpec>,
+        response: Sender<anyhow::Result<()>>,
     },
+    Faults {
+        response: Sender<Vec<SystemPanic>>,
+    },
+    Shutdown,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Settings {
+    /// Simulated seconds per tick.
+    pub timestep: f64,
+    /// Messages the handle may queue ahead of the simulation thread before calls block.
+    pub queue_capacity: usize,
+    /// What to do when a system panics.
+    pub on_panic: OnPanic,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            timestep: 1.0 / 60.0,
+            queue_capacity: 1024,
+            on_panic: OnPanic::Halt,
+        }
+    }
+}
+
+/// Handle to a simulation running on its own thread. Dropping it shuts the simulation down
+/// like `shutdown`, discarding the result.
 pub struct TECS {
-    thread: JoinHandle<()>,
-    tx: Sender<Message>,
+    thread: Option<JoinHandle<anyhow::Result<()>>>,
+    tx: SyncSender<Message>,
+    /// Why the simulation thread stopped, if it panicked outside a system.
+    stopped: Arc<Mutex<Option<String>>>,
 }
 impl TECS {
     pub fn new() -> Self {
-        let (tx, rx) = mpsc::channel();
-        let thread = std::thread::spawn(move || {
-            let mut ecs = World::new();
-            while let Ok(cmd) = rx.recv() {
-                match cmd {
-                    Message::Tick => {
-                        ecs.run_systems();
-                    }
-                    Message::Create { spawn_fn, response } => {
-                        let e = spawn_fn(&mut ecs);
-                        response.send(e).unwrap();
-                    }
-                    Message::Delete { entity } => {
-                        ecs.despawn(entity).expect("Could not despawn entity.")
-                    }
-                    Message::Systems { systems } => {
-                        ecs.add_systems(systems);
-                    }
-                }
-            }
+        Self::with_settings(Settings::default())
+    }
+    /// Starts a simulation advancing by `dt` simulated seconds per tick.
+    pub fn with_timestep(dt: f64) -> Self {
+        Self::with_settings(Settings {
+            timestep: dt,
+            ..Settings::default()
+        })
+    }
+    pub fn with_settings(settings: Settings) -> Self {
+        let (tx, rx) = mpsc::sync_channel(settings.queue_capacity);
+        let stopped = Arc::new(Mutex::new(None));
+        let thread = std::thread::spawn({
+            let stopped = stopped.clone();
+            move || Worker::run(rx, settings, stopped)
         });
-        Self { thread, tx }
+        Self {
+            thread: Some(thread),
+            tx,
+            stopped,
+        }
+    }
+    /// Handles every queued message, then stops the simulation thread. Fails with the panic
+    /// that halted the simulation, if any.
+    pub fn shutdown(mut self) -> anyhow::Result<()> {
+        self.stop()
+    }
+    fn stop(&mut self) -> anyhow::Result<()> {
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        let _ = self.tx.send(Message::Shutdown);
+        thread.join().map_err(|payload| {
+            anyhow!("Simulation thread panicked: {}", panic_message(&*payload))
+        })?
+    }
+    /// Why the simulation thread is gone, waiting for it to finish unwinding.
+    fn stop_cause(&self) -> String {
+        if let Some(thread) = &self.thread {
+            while !thread.is_finished() {
+                std::thread::yield_now();
+            }
+        }
+        self.stopped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_else(|| "unknown cause".to_string())
     }
     fn send(&self, message: Message) {
-        self.tx.send(message).expect("Could not send message.");
+        if self.tx.send(message).is_err() {
+            panic!("Simulation thread stopped: {}", self.stop_cause());
+        }
+    }
+    fn receive<T>(&self, rx: mpsc::Receiver<T>) -> T {
+        rx.recv()
+            .unwrap_or_else(|_| panic!("Simulation thread stopped: {}", self.stop_cause()))
+    }
+    /// Queues a tick, whether or not the simulation is paused. Blocks while the message queue
+    /// is full.
+    pub fn tick(&self) -> TickHandle {
+        self.tick_n(1)
+    }
+    /// Queues `ticks` ticks, run back to back.
+    pub fn tick_n(&self, ticks: u64) -> TickHandle {
+        let (done, receiver) = tokio::sync::oneshot::channel();
+        self.send(Message::Tick { ticks, done });
+        TickHandle::new(receiver)
+    }
+    /// Runs a single tick, like `tick`.
+    pub fn step(&self) -> TickHandle {
+        self.tick()
     }
-    pub fn tick(&self) {
-        self.send(Message::Tick);
+    /// Waits until every message queued before this call has been handled.
+    pub fn sync(&self) {
+        self.with_world(|_| ());
+    }
+    /// Keeps ticking in the background until paused or given another run mode.
+    pub fn run(&self) {
+        self.send(Message::Run);
+    }
+    /// Ticks `ticks` more times in the background, then waits for messages again.
+    pub fn run_for(&self, ticks: u64) {
+        self.send(Message::RunFor(ticks));
+    }
+    /// Ticks in the background until `predicate` holds after a tick.
+    pub fn run_until(&self, predicate: impl FnMut(&World) -> bool + Send + 'static) {
+        self.send(Message::RunUntil(Box::new(predicate)));
+    }
+    /// Suspends the current run mode. Single ticks still run.
+    pub fn pause(&self) {
+        self.send(Message::Pause);
+    }
+    pub fn resume(&self) {
+        self.send(Message::Resume);
+    }
+    /// Fails on a real-time pacing whose `time_scale` is not a positive, finite number.
+    pub fn set_pacing(&self, pacing: Pacing) -> anyhow::Result<()> {
+        if let Pacing::RealTime { time_scale } = pacing {
+            anyhow::ensure!(
+                time_scale > 0.0 && time_scale.is_finite(),
+                "Time scale must be positive and finite, found {time_scale}."
+            );
+        }
+        self.send(Message::Pacing(pacing));
+        Ok(())
     }
     pub fn create_entity(&self, components: impl hecs::DynamicBundle + Send + 'static) -> EntityID {
         let (tx, rx) = std::sync::mpsc::channel();
         let boxed_components = Box::new(components);
         self.send(Message::Create {
-            spawn_fn: Box::new(|world| {
-                return world.spawn(*boxed_components);
-            }),
+            spawn_fn: Box::new(|world| world.spawn(*boxed_components)),
             response: tx,
         });
-        rx.recv()
-            .expect("Could not receive EntityID object after creation.")
+        self.receive(rx)
     }
     pub fn remove_entity(&self, entity: EntityID) {
         self.send(Message::Delete { entity });
     }
-    pub fn add_systems(&self, systems: Vec<impl System + Sync + 'static>) {
-        let boxed_systems = systems
-            .into_iter()
-            .map(|s| Box::new(s) as Box<dyn System>)
-            .collect();
+    /// Removes an entity and all its descendants.
+    pub fn remove_entity_recursive(&self, entity: EntityID) -> anyhow::Result<()> {
+        self.with_world(move |world| world.despawn_recursive(entity))
+    }
+    /// Hangs `child` from `parent`, like `World::set_parent`.
+    pub fn set_parent(&self, child: EntityID, parent: EntityID) -> anyhow::Result<()> {
+        self.with_world(move |world| world.set_parent(child, parent))
+    }
+    /// Spawns `count` instances of `prefab`, adding `overrides(i)` on top of the components of
+    /// instance `i`.
+    pub fn spawn_prefabs<B: hecs::DynamicBundle>(
+        &self,
+        prefabs: Arc<Prefabs>,
+        prefab: &str,
+        count: usize,
+        mut overrides: impl FnMut(usize) -> B + Send + 'static,
+    ) -> anyhow::Result<Vec<EntityID>> {
+        let prefab = prefab.to_string();
+        self.with_world(move |world| {
+            (0..count)
+                .map(|i| prefabs.spawn(world, &prefab, overrides(i)))
+                .collect()
+        })
+    }
+    /// Runs `f` on the simulation thread between ticks and hands back its result. A panic in
+    /// `f` stops the simulation thread.
+    pub fn with_world<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut World) -> R + Send + 'static,
+    ) -> R {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.send(Message::Apply(Box::new(move |world| {
+            let _ = tx.send(f(world));
+        })));
+        self.receive(rx)
+    }
+    /// Adds or replaces components of an existing entity.
+    pub fn insert_components(
+        &self,
+        entity: EntityID,
+        components: impl hecs::DynamicBundle + Send + 'static,
+    ) -> anyhow::Result<()> {
+        self.with_world(move |world| world.insert(entity, components))
+    }
+    /// Takes the components `T` off an entity. Fails unless it has all of them.
+    pub fn remove_components<T: hecs::Bundle + Send + 'static>(
+        &self,
+        entity: EntityID,
+    ) -> anyhow::Result<T> {
+        self.with_world(move |world| world.remove::<T>(entity))
+    }
+    pub fn contains(&self, entity: EntityID) -> bool {
+        self.with_world(move |world| world.contains(entity))
+    }
+    /// Clones the components `T`, a tuple, out of an entity, if it has all of them.
+    pub fn fetch<T: Fetch + Send + 'static>(&self, entity: EntityID) -> Option<T> {
+        self.with_world(move |world| world.fetch::<T>(entity))
+    }
+    /// Runs `f` on the simulation thread before the next tick, without waiting for it.
+    pub(crate) fn between_ticks(&self, f: impl FnOnce(&mut World) + Send + 'static) {
+        self.send(Message::Apply(Box::new(f)));
+    }
+    /// Sets the world's `T` before the next tick.
+    pub fn insert_resource<T: Send + Sync + 'static>(&self, value: T) {
+        self.send(Message::Apply(Box::new(|world| {
+            world.insert_resource(value);
+        })));
+    }
+    pub fn add_event<E: Send + Sync + 'static>(&self) {
+        self.send(Message::Apply(Box::new(World::add_event::<E>)));
+    }
+    pub fn track<T: hecs::Component + Clone + PartialEq>(&self) {
+        self.send(Message::Apply(Box::new(World::track::<T>)));
+    }
+    /// Takes the events of type `E` still buffered after the ticks queued so far.
+    pub fn drain_events<E: Send + Sync + 'static>(&self) -> Vec<E> {
+        self.with_world(World::drain_events::<E>)
+    }
+    /// Adds systems to the schedule. Within a stage, systems run in the order they were added
+    /// unless their `before`/`after` constraints say otherwise, and systems whose declared
+    /// accesses don't conflict run in parallel. Constraints on systems that are added later
+    /// take effect once they are. Fails, adding nothing, if the constraints form a cycle or
+    /// order a system before one in an earlier stage.
+    pub fn add_systems(&self, systems: Vec<impl Into<SystemSpec>>) -> anyhow::Result<()> {
+        let (tx, rx) = std::sync::mpsc::channel();
         self.send(Message::Systems {
-            systems: boxed_systems,
+            systems: systems.into_iter().map(Into::into).collect(),
+            response: tx,
         });
+        self.receive(rx)
+    }
+    /// Every system panic so far, including those of systems since disabled.
+    pub fn faults(&self) -> Vec<SystemPanic> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.send(Message::Faults { response: tx });
+        self.receive(rx)
+    }
+}
+impl Drop for TECS {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+impl Default for TECS {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -127,10 +501,11 @@ fn test() {
     let tecs = TECS::new();
     tecs.tick();
     tecs.create_entity((43u32, "hello!"));
-    tecs.add_systems(vec![test_system]);
+    tecs.add_systems(vec![test_system]).unwrap();
     tecs.tick();
 }
 
+#[cfg(test)]
 fn test_system(world: &mut World) {
     let mut entities = world.query::<&u32>();
     for e in entities.iter() {
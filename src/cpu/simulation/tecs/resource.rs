@@ -0,0 +1,89 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    collections::HashMap,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError},
+};
+
+type Boxed = Box<dyn Any + Send + Sync>;
+
+/// Singletons of a world, one per type, borrow-checked at runtime so that systems sharing
+/// `&World` can use them.
+#[derive(Default)]
+pub(crate) struct Resources {
+    inner: HashMap<TypeId, RwLock<Boxed>>,
+}
+impl Resources {
+    pub(crate) fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        let old = self
+            .inner
+            .insert(TypeId::of::<T>(), RwLock::new(Box::new(value)))?;
+        Some(Self::unbox(old))
+    }
+    pub(crate) fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        let old = self.inner.remove(&TypeId::of::<T>())?;
+        Some(Self::unbox(old))
+    }
+    fn unbox<T: 'static>(lock: RwLock<Boxed>) -> T {
+        let value = lock.into_inner().unwrap_or_else(|e| e.into_inner());
+        *value
+            .downcast()
+            .expect("Resource stored under the wrong type.")
+    }
+    pub(crate) fn get<T: 'static>(&self) -> Option<Res<'_, T>> {
+        let guard = match self.inner.get(&TypeId::of::<T>())?.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                panic!("Resource {} is already borrowed mutably.", type_name::<T>())
+            }
+        };
+        Some(Res {
+            guard,
+            types: PhantomData,
+        })
+    }
+    pub(crate) fn get_mut<T: 'static>(&self) -> Option<ResMut<'_, T>> {
+        let guard = match self.inner.get(&TypeId::of::<T>())?.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                panic!("Resource {} is already borrowed.", type_name::<T>())
+            }
+        };
+        Some(ResMut {
+            guard,
+            types: PhantomData,
+        })
+    }
+}
+
+/// Shared borrow of a resource, returned by `World::resource`.
+pub struct Res<'a, T> {
+    guard: RwLockReadGuard<'a, Boxed>,
+    types: PhantomData<&'a T>,
+}
+impl<T: 'static> Deref for Res<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.guard.downcast_ref().unwrap()
+    }
+}
+
+/// Exclusive borrow of a resource, returned by `World::resource_mut`.
+pub struct ResMut<'a, T> {
+    guard: RwLockWriteGuard<'a, Boxed>,
+    types: PhantomData<&'a mut T>,
+}
+impl<T: 'static> Deref for ResMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.guard.downcast_ref().unwrap()
+    }
+}
+impl<T: 'static> DerefMut for ResMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.guard.downcast_mut().unwrap()
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    panic::{AssertUnwindSafe, catch_unwind},
+};
+
+use petgraph::{
+    Direction,
+    algo::tarjan_scc,
+    graph::{DiGraph, NodeIndex},
+};
+
+use crate::cpu::simulation::tecs::{
+    World, change,
+    command::as_system,
+    fault::{OnPanic, SystemPanic, panic_message},
+    system::{Run, SystemSpec},
+};
+
+/// Labelled phases of a tick, run in declaration order.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum Stage {
+    PreUpdate,
+    #[default]
+    Update,
+    PostUpdate,
+}
+impl Stage {
+    pub const ALL: [Stage; 3] = [Stage::PreUpdate, Stage::Update, Stage::PostUpdate];
+}
+
+/// Every system of a world, compiled into batches of mutually non-conflicting systems.
+#[derive(Default)]
+pub(crate) struct Schedule {
+    systems: Vec<SystemSpec>,
+    batches: Vec<Vec<usize>>,
+}
+impl Schedule {
+    /// Adds `systems` and recompiles the schedule, leaving it untouched if the constraints
+    /// can't be satisfied.
+    pub(crate) fn add_systems(&mut self, systems: Vec<SystemSpec>) -> anyhow::Result<()> {
+        let count = self.systems.len();
+        self.systems
+            .extend(systems.into_iter().enumerate().map(|(i, mut system)| {
+                if !system.named {
+                    system.name = format!("{} #{}", system.name, count + i);
+                }
+                system
+            }));
+        match self.compile() {
+            Ok(batches) => {
+                self.batches = batches;
+                Ok(())
+            }
+            Err(e) => {
+                self.systems.truncate(count);
+                Err(e)
+            }
+        }
+    }
+    /// Orders each stage by its `before`/`after` constraints, ignoring those on systems that
+    /// haven't been added yet and breaking ties by insertion order, then puts every system in
+    /// the batch after its last predecessor or earlier conflicting system. Conflicting systems
+    /// therefore never share a batch and always run in the same order, so the results don't
+    /// depend on threading.
+    fn compile(&self) -> anyhow::Result<Vec<Vec<usize>>> {
+        let mut graph = DiGraph::<usize, ()>::new();
+        let nodes = (0..self.systems.len())
+            .map(|i| graph.add_node(i))
+            .collect::<Vec<_>>();
+        for (i, system) in self.systems.iter().enumerate() {
+            let constraints = system.before.iter().map(|name| (name, true));
+            for (name, before) in constraints.chain(system.after.iter().map(|name| (name, false))) {
+                for j in self.named(name) {
+                    let (first, then) = if before { (i, j) } else { (j, i) };
+                    let (a, b) = (&self.systems[first], &self.systems[then]);
+                    anyhow::ensure!(
+                        a.stage <= b.stage,
+                        "System {} in {:?} cannot run before system {} in {:?}.",
+                        a.name,
+                        a.stage,
+                        b.name,
+                        b.stage
+                    );
+                    graph.update_edge(nodes[first], nodes[then], ());
+                }
+            }
+        }
+        if let Some(cycle) = tarjan_scc(&graph)
+            .into_iter()
+            .find(|scc| scc.len() > 1 || graph.contains_edge(scc[0], scc[0]))
+        {
+            let mut names = cycle
+                .iter()
+                .map(|&n| self.systems[graph[n]].name.as_str())
+                .collect::<Vec<_>>();
+            names.sort();
+            anyhow::bail!(
+                "Cyclic ordering constraints between systems: {}.",
+                names.join(", ")
+            );
+        }
+
+        let order = Self::order(&graph, &self.systems);
+        let mut levels = vec![0; self.systems.len()];
+        for (position, &i) in order.iter().enumerate() {
+            let predecessors = graph
+                .neighbors_directed(nodes[i], Direction::Incoming)
+                .map(|n| graph[n]);
+            let conflicting = order[..position]
+                .iter()
+                .copied()
+                .filter(|&j| self.systems[j].access.conflicts(&self.systems[i].access));
+            levels[i] = predecessors
+                .chain(conflicting)
+                .map(|j| levels[j] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+        let mut batches = Vec::new();
+        for stage in Stage::ALL {
+            let in_stage = order
+                .iter()
+                .copied()
+                .filter(|&i| self.systems[i].stage == stage)
+                .collect::<Vec<_>>();
+            let first = in_stage.iter().map(|&i| levels[i]).min().unwrap_or(0);
+            let last = in_stage.iter().map(|&i| levels[i]).max();
+            for level in first..last.map_or(first, |l| l + 1) {
+                let batch = in_stage
+                    .iter()
+                    .copied()
+                    .filter(|&i| levels[i] == level)
+                    .collect::<Vec<_>>();
+                if !batch.is_empty() {
+                    batches.push(batch);
+                }
+            }
+        }
+        Ok(batches)
+    }
+    /// Systems given `name` through `SystemSpec::named`.
+    fn named(&self, name: &str) -> Vec<usize> {
+        (0..self.systems.len())
+            .filter(|&i| self.systems[i].named && self.systems[i].name == name)
+            .collect()
+    }
+    /// Topological order of an acyclic graph, by stage then earliest insertion first.
+    fn order(graph: &DiGraph<usize, ()>, systems: &[SystemSpec]) -> Vec<usize> {
+        let mut waiting = graph
+            .node_indices()
+            .map(|n| graph.neighbors_directed(n, Direction::Incoming).count())
+            .collect::<Vec<_>>();
+        let key = |n: NodeIndex| Reverse((systems[graph[n]].stage, graph[n]));
+        let mut ready = graph
+            .node_indices()
+            .filter(|n| waiting[n.index()] == 0)
+            .map(|n| (key(n), n))
+            .collect::<BinaryHeap<_>>();
+        let mut order = Vec::with_capacity(systems.len());
+        while let Some((_, n)) = ready.pop() {
+            order.push(graph[n]);
+            for next in graph.neighbors_directed(n, Direction::Outgoing) {
+                waiting[next.index()] -= 1;
+                if waiting[next.index()] == 0 {
+                    ready.push((key(next), next));
+                }
+            }
+        }
+        order
+    }
+    /// Runs one tick's worth of systems. A panicking system under `OnPanic::Halt` abandons the
+    /// tick with an error. Under `OnPanic::Disable` it is disabled, its panic is added to
+    /// `disabled` and the tick carries on.
+    pub(crate) fn run(
+        &mut self,
+        world: &mut World,
+        on_panic: OnPanic,
+        disabled: &mut Vec<SystemPanic>,
+    ) -> Result<(), SystemPanic> {
+        for batch in &self.batches {
+            let mut systems = self
+                .systems
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, system)| batch.contains(i) && !system.disabled)
+                .map(|(i, system)| (i, system.last_run, &mut system.run))
+                .collect::<Vec<_>>();
+            let mut panics = match systems.as_mut_slice() {
+                [] => Vec::new(),
+                [(i, last_run, Run::Exclusive(system))] => {
+                    vec![(*i, guard(*i, *last_run, || system(world)))]
+                }
+                [(i, last_run, Run::Shared(system))] => {
+                    vec![(*i, guard(*i, *last_run, || system(world)))]
+                }
+                [(i, last_run, head), rest @ ..] => {
+                    let world = &*world;
+                    std::thread::scope(|scope| {
+                        let handles = rest
+                            .iter_mut()
+                            .map(|(i, last_run, system)| {
+                                let (i, last_run) = (*i, *last_run);
+                                scope
+                                    .spawn(move || (i, guard(i, last_run, || system.shared(world))))
+                            })
+                            .collect::<Vec<_>>();
+                        let mut results = vec![(*i, guard(*i, *last_run, || head.shared(world)))];
+                        results.extend(handles.into_iter().map(|h| h.join().unwrap()));
+                        results
+                    })
+                }
+            };
+            let commanded = world.apply_commands();
+            world.detect_changes(|component| {
+                commanded
+                    || batch
+                        .iter()
+                        .any(|&i| self.systems[i].access.may_write(component))
+            });
+            for &i in batch {
+                self.systems[i].last_run = world.changes.tick();
+            }
+            panics.retain(|(_, result)| result.is_err());
+            panics.sort_by_key(|(i, _)| *i);
+            for (i, result) in panics {
+                let fault = SystemPanic {
+                    system: self.systems[i].name.clone(),
+                    tick: world.time().tick(),
+                    message: result.unwrap_err(),
+                };
+                match self.systems[i].on_panic.unwrap_or(on_panic) {
+                    OnPanic::Halt => return Err(fault),
+                    OnPanic::Disable => {
+                        self.systems[i].disabled = true;
+                        disabled.push(fault);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs the system at `position`, which last ran at change tick `last_run`, catching its
+/// panic.
+fn guard(position: usize, last_run: u64, system: impl FnOnce()) -> Result<(), String> {
+    catch_unwind(AssertUnwindSafe(|| {
+        as_system(position, || change::since(last_run, system))
+    }))
+    .map_err(|payload| panic_message(&*payload))
+}
@@ -0,0 +1,291 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use mlua::{
+    AnyUserData, FromLua, IntoLua, Lua, MetaMethod, Table, UserData, UserDataFields,
+    UserDataMethods, Value,
+};
+
+use crate::cpu::maths::{matrix::Matrix, vector::Vector};
+
+type Operator<T> = fn(T, T) -> T;
+
+/// Adds the maths types to a Lua state:
+///
+/// - `vec3(x, y, z)` makes a native Luau vector, with the `vector` library's functions, such as
+///   `dot` and `cross`, available as methods.
+/// - `vec2(x, y)` and `vec4(x, y, z, w)` make userdata with `x`..`w` fields, `dot`, `sum` and
+///   `prod` methods, and component-wise operators that also take numbers.
+/// - `mat({ {a, b}, {c, d} })` makes a matrix of up to 4 by 4 from its rows, with `rows`
+///   and `cols` fields, `get(i, j)`, `row(i)`, `transpose`, `dot`, `sum` and `prod` methods,
+///   component-wise operators, and `matmul`, which multiplies by a matrix or a column vector.
+///
+/// Indices are 1-based, as usual in Lua. Each type converts to and from the matching Rust type
+/// through `IntoLua` and `FromLua`, so components can hold them.
+pub fn register(lua: &Lua) -> mlua::Result<()> {
+    let globals = lua.globals();
+    globals.set(
+        "vec2",
+        lua.create_function(|_, (x, y): (f32, f32)| Ok(Vector::new([x, y])))?,
+    )?;
+    globals.set(
+        "vec3",
+        lua.create_function(|_, (x, y, z): (f32, f32, f32)| Ok(Vector::new([x, y, z])))?,
+    )?;
+    globals.set(
+        "vec4",
+        lua.create_function(|_, (x, y, z, w): (f32, f32, f32, f32)| Ok(Vector::new([x, y, z, w])))?,
+    )?;
+    globals.set("mat", lua.create_function(matrix)?)?;
+    if let Ok(library) = globals.get::<Table>("vector") {
+        let metatable = lua.create_table()?;
+        metatable.set("__index", library)?;
+        lua.set_type_metatable::<mlua::Vector>(Some(metatable));
+    }
+    Ok(())
+}
+
+fn components<const N: usize>(vector: &Vector<N>) -> [f32; N] {
+    std::array::from_fn(|i| vector[i])
+}
+
+/// 3-vectors are native Luau vectors; the other sizes are `LuaVector` userdata.
+impl<const N: usize> IntoLua for Vector<N> {
+    fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+        match N {
+            3 => Ok(Value::Vector(mlua::Vector::new(self[0], self[1], self[2]))),
+            _ => LuaVector(self).into_lua(lua),
+        }
+    }
+}
+impl<const N: usize> FromLua for Vector<N> {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        match &value {
+            Value::Vector(vector) if N == 3 => {
+                let xyz = [vector.x(), vector.y(), vector.z()];
+                return Ok(Vector::new(std::array::from_fn(|i| xyz[i])));
+            }
+            Value::UserData(data) => {
+                if let Ok(vector) = data.borrow::<LuaVector<N>>() {
+                    return Ok(vector.0);
+                }
+            }
+            _ => {}
+        }
+        Err(mlua::Error::FromLuaConversionError {
+            from: value.type_name(),
+            to: format!("vec{N}"),
+            message: None,
+        })
+    }
+}
+
+/// A vector operand, where numbers stand for a vector of that number.
+fn vector_operand<const N: usize>(value: Value, lua: &Lua) -> mlua::Result<Vector<N>> {
+    match value {
+        Value::Integer(n) => Ok(Vector::new([n as f32; N])),
+        Value::Number(n) => Ok(Vector::new([n as f32; N])),
+        value => Vector::from_lua(value, lua),
+    }
+}
+
+#[derive(Clone, Copy)]
+struct LuaVector<const N: usize>(Vector<N>);
+impl<const N: usize> UserData for LuaVector<N> {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        for (i, name) in ["x", "y", "z", "w"].into_iter().take(N).enumerate() {
+            fields.add_field_method_get(name, move |_, this| Ok(this.0[i]));
+            fields.add_field_method_set(name, move |_, this, value: f32| {
+                let mut components = components(&this.0);
+                components[i] = value;
+                this.0 = Vector::new(components);
+                Ok(())
+            });
+        }
+    }
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("dot", |_, this, other: Vector<N>| Ok(this.0.dot(&other)));
+        methods.add_method("sum", |_, this, ()| Ok(this.0.sum()));
+        methods.add_method("prod", |_, this, ()| Ok(this.0.prod()));
+        let operators: [(MetaMethod, Operator<Vector<N>>); 4] = [
+            (MetaMethod::Add, Add::add),
+            (MetaMethod::Sub, Sub::sub),
+            (MetaMethod::Mul, Mul::mul),
+            (MetaMethod::Div, Div::div),
+        ];
+        for (method, operator) in operators {
+            methods.add_meta_function(method, move |lua, (a, b): (Value, Value)| {
+                Ok(operator(
+                    vector_operand::<N>(a, lua)?,
+                    vector_operand::<N>(b, lua)?,
+                ))
+            });
+        }
+        methods.add_meta_method(MetaMethod::Unm, |_, this, ()| {
+            Ok(Vector::new([0.0; N]) - this.0)
+        });
+        methods.add_meta_method(MetaMethod::Eq, |_, this, other: Vector<N>| {
+            Ok(this.0 == other)
+        });
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            Ok(format!("vec{N}{:?}", components(&this.0)))
+        });
+    }
+}
+
+/// Builds a matrix from a table of rows, choosing its type from the shape.
+fn matrix(lua: &Lua, rows: Vec<Vec<f32>>) -> mlua::Result<Value> {
+    let (nx, ny) = (rows.first().map_or(0, Vec::len), rows.len());
+    if rows.iter().any(|row| row.len() != nx) {
+        return Err(mlua::Error::runtime(
+            "Matrix rows must have the same length.",
+        ));
+    }
+    macro_rules! shapes {
+        ($(($nx:literal, $ny:literal)),*) => {
+            match (nx, ny) {
+                $(($nx, $ny) => Matrix::<$nx, $ny>::new(std::array::from_fn(|i| {
+                    std::array::from_fn(|j| rows[i][j])
+                }))
+                .into_lua(lua),)*
+                _ => Err(mlua::Error::runtime(format!(
+                    "Matrices must be 1 to 4 by 1 to 4, not {ny} by {nx}."
+                ))),
+            }
+        };
+    }
+    shapes!(
+        (1, 1),
+        (2, 1),
+        (3, 1),
+        (4, 1),
+        (1, 2),
+        (2, 2),
+        (3, 2),
+        (4, 2),
+        (1, 3),
+        (2, 3),
+        (3, 3),
+        (4, 3),
+        (1, 4),
+        (2, 4),
+        (3, 4),
+        (4, 4)
+    )
+}
+
+impl<const NX: usize, const NY: usize> FromLua for Matrix<NX, NY> {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        if let Value::UserData(data) = &value
+            && let Ok(matrix) = data.borrow::<Self>()
+        {
+            return Ok(*matrix);
+        }
+        Err(mlua::Error::FromLuaConversionError {
+            from: value.type_name(),
+            to: format!("mat{NY}x{NX}"),
+            message: None,
+        })
+    }
+}
+
+/// A matrix operand, where numbers stand for a matrix of that number.
+fn matrix_operand<const NX: usize, const NY: usize>(
+    value: Value,
+    lua: &Lua,
+) -> mlua::Result<Matrix<NX, NY>> {
+    match value {
+        Value::Integer(n) => Ok(Matrix::new([[n as f32; NX]; NY])),
+        Value::Number(n) => Ok(Matrix::new([[n as f32; NX]; NY])),
+        value => Matrix::from_lua(value, lua),
+    }
+}
+
+/// Multiplies by a matrix of any width with `NX` rows, or by a column vector of `NX`.
+fn matmul<const NX: usize, const NY: usize>(
+    lua: &Lua,
+    lhs: &Matrix<NX, NY>,
+    rhs: Value,
+) -> mlua::Result<Value> {
+    if let Value::UserData(data) = &rhs {
+        macro_rules! widths {
+            ($($nz:literal),*) => {
+                $(if let Ok(rhs) = data.borrow::<Matrix<$nz, NX>>() {
+                    return (*lhs | *rhs).into_lua(lua);
+                })*
+            };
+        }
+        widths!(1, 2, 3, 4);
+        if is_matrix(data) {
+            return Err(mlua::Error::runtime(format!(
+                "Cannot multiply a {NY} by {NX} matrix by a matrix without {NX} rows."
+            )));
+        }
+    }
+    let vector = Vector::<NX>::from_lua(rhs, lua)?;
+    Vector::<NY>::new(std::array::from_fn(|i| lhs.row(i).dot(&vector))).into_lua(lua)
+}
+
+/// Whether `data` is a matrix of any shape.
+fn is_matrix(data: &AnyUserData) -> bool {
+    macro_rules! heights {
+        ($($ny:literal),*) => {
+            false $(
+                || data.is::<Matrix<1, $ny>>()
+                || data.is::<Matrix<2, $ny>>()
+                || data.is::<Matrix<3, $ny>>()
+                || data.is::<Matrix<4, $ny>>()
+            )*
+        };
+    }
+    heights!(1, 2, 3, 4)
+}
+
+/// Checks a 1-based index against `len`, returning it 0-based.
+fn index(i: usize, len: usize) -> mlua::Result<usize> {
+    match i {
+        1.. if i <= len => Ok(i - 1),
+        _ => Err(mlua::Error::runtime(format!(
+            "Index {i} is out of range 1 to {len}."
+        ))),
+    }
+}
+
+impl<const NX: usize, const NY: usize> UserData for Matrix<NX, NY> {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field("rows", NY);
+        fields.add_field("cols", NX);
+    }
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get", |_, this, (i, j): (usize, usize)| {
+            Ok(this.row(index(i, NY)?)[index(j, NX)?])
+        });
+        methods.add_method("row", |_, this, i: usize| Ok(*this.row(index(i, NY)?)));
+        methods.add_method("transpose", |_, this, ()| Ok(this.transpose()));
+        methods.add_method("dot", |_, this, other: Self| Ok(this.dot(&other)));
+        methods.add_method("sum", |_, this, ()| Ok(this.sum()));
+        methods.add_method("prod", |_, this, ()| Ok(this.prod()));
+        methods.add_method("matmul", |lua, this, rhs: Value| matmul(lua, this, rhs));
+        let operators: [(MetaMethod, Operator<Self>); 4] = [
+            (MetaMethod::Add, Add::add),
+            (MetaMethod::Sub, Sub::sub),
+            (MetaMethod::Mul, Mul::mul),
+            (MetaMethod::Div, Div::div),
+        ];
+        for (method, operator) in operators {
+            methods.add_meta_function(method, move |lua, (a, b): (Value, Value)| {
+                Ok(operator(
+                    matrix_operand::<NX, NY>(a, lua)?,
+                    matrix_operand::<NX, NY>(b, lua)?,
+                ))
+            });
+        }
+        methods.add_meta_method(MetaMethod::Unm, |_, this, ()| {
+            Ok(Matrix::new([[0.0; NX]; NY]) - *this)
+        });
+        methods.add_meta_method(MetaMethod::Eq, |_, this, other: Self| Ok(*this == other));
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            let rows = this.inner.iter().map(components).collect::<Vec<_>>();
+            Ok(format!("mat{NY}x{NX}{rows:?}"))
+        });
+    }
+}
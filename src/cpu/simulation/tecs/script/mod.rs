@@ -0,0 +1,299 @@
+use std::{
+    ffi::c_void,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use mlua::{
+    FromLua, Function, IntoLua, LightUserData, Lua, MultiValue, Table, UserData, UserDataFields,
+    UserDataMethods, Value, Variadic,
+};
+
+use crate::{
+    cpu::simulation::tecs::{
+        EntityID, World,
+        fault::OnPanic,
+        script::{
+            registry::Registry,
+            sandbox::{Limits, Sandbox},
+        },
+        system::SystemSpec,
+    },
+    reload::Replacement,
+};
+
+pub mod maths;
+pub mod prefab;
+pub mod registry;
+pub mod sandbox;
+
+/// A system written in Luau. The script evaluates to its system function, which is called
+/// every tick with the world:
+///
+/// ```lua
+/// return function(world)
+///     for entity, position, velocity in world:query("Position", "Velocity") do
+///         position.x += velocity.x * world.dt
+///         world:set(entity, "Position", position)
+///     end
+/// end
+/// ```
+///
+/// Besides `query`, the world offers `get(entity, name)`, `set(entity, name, value)`,
+/// `remove(entity, name)`, `spawn({ [name] = value })`, `despawn(entity)` and `resource(name)`,
+/// and the `tick`, `dt` and `elapsed` fields. Components and resources are named by the
+/// `Registry` and copied in and out, so changes only take effect through `set`.
+///
+/// Scripts from untrusted sources should be `sandboxed`: they then lose `os`, `debug` and
+/// `require`, run within `Limits`, and are disabled rather than halting the simulation when
+/// they fail, whatever `Settings::on_panic` says.
+pub struct ScriptSystem {
+    name: String,
+    lua: Lua,
+    sandbox: Option<Sandbox>,
+    function: Arc<Mutex<Function>>,
+    registry: Arc<Registry>,
+}
+impl ScriptSystem {
+    pub fn new(
+        name: impl Into<String>,
+        source: &str,
+        registry: Arc<Registry>,
+    ) -> anyhow::Result<Self> {
+        let lua = Lua::new();
+        maths::register(&lua)?;
+        Self::build(name.into(), source, registry, lua, None)
+    }
+    /// Loads a script from a file, named after the file.
+    pub fn load(path: &str, registry: Arc<Registry>) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        Self::new(file_name(path), &source, registry)
+    }
+    pub fn sandboxed(
+        name: impl Into<String>,
+        source: &str,
+        registry: Arc<Registry>,
+        limits: Limits,
+    ) -> anyhow::Result<Self> {
+        let (lua, sandbox) = Sandbox::new(limits)?;
+        Self::build(name.into(), source, registry, lua, Some(sandbox))
+    }
+    pub fn load_sandboxed(
+        path: &str,
+        registry: Arc<Registry>,
+        limits: Limits,
+    ) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        Self::sandboxed(file_name(path), &source, registry, limits)
+    }
+    fn build(
+        name: String,
+        source: &str,
+        registry: Arc<Registry>,
+        lua: Lua,
+        sandbox: Option<Sandbox>,
+    ) -> anyhow::Result<Self> {
+        let function = compile(&lua, sandbox.as_ref(), &name, source)?;
+        Ok(Self {
+            name,
+            lua,
+            sandbox,
+            function: Arc::new(Mutex::new(function)),
+            registry,
+        })
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// A handle for replacing the system function, even once the system is scheduled.
+    pub fn reloader(&self) -> ScriptReloader {
+        ScriptReloader {
+            name: self.name.clone(),
+            lua: self.lua.clone(),
+            sandbox: self.sandbox.clone(),
+            function: self.function.clone(),
+        }
+    }
+    /// Calls the system function on `world`.
+    pub fn run(&mut self, world: &mut World) -> anyhow::Result<()> {
+        let registry = &*self.registry;
+        let function = self
+            .function
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.start();
+        }
+        self.lua.scope(|scope| {
+            let world = scope.create_userdata(ScriptWorld { world, registry })?;
+            function.call::<()>(world)
+        })?;
+        Ok(())
+    }
+}
+
+/// Replaces the function of a `ScriptSystem`.
+#[derive(Clone)]
+pub struct ScriptReloader {
+    name: String,
+    lua: Lua,
+    sandbox: Option<Sandbox>,
+    function: Arc<Mutex<Function>>,
+}
+impl ScriptReloader {
+    /// Compiles `source` in the script's Lua state. The current function stays in use until
+    /// the returned replacement is applied, and for good if compiling fails.
+    pub fn reload(&self, source: &str) -> anyhow::Result<Replacement> {
+        let function = compile(&self.lua, self.sandbox.as_ref(), &self.name, source)?;
+        let target = self.function.clone();
+        Ok(Replacement::new(move || {
+            *target.lock().unwrap_or_else(|e| e.into_inner()) = function;
+        }))
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map_or(path.into(), |stem| stem.to_string_lossy().into_owned())
+}
+
+/// Evaluates a script, which must return its system function.
+fn compile(
+    lua: &Lua,
+    sandbox: Option<&Sandbox>,
+    name: &str,
+    source: &str,
+) -> anyhow::Result<Function> {
+    if let Some(sandbox) = sandbox {
+        sandbox.start();
+    }
+    match lua.load(source).set_name(name).eval::<Value>()? {
+        Value::Function(function) => Ok(function),
+        other => anyhow::bail!(
+            "Script {name} must return its system function, not a {}.",
+            other.type_name()
+        ),
+    }
+}
+
+/// Runs the script as an exclusive system named after it, which panics if the script fails.
+impl From<ScriptSystem> for SystemSpec {
+    fn from(mut script: ScriptSystem) -> Self {
+        let name = script.name.clone();
+        let sandboxed = script.sandbox.is_some();
+        let spec = SystemSpec::exclusive(move |world: &mut World| {
+            if let Err(e) = script.run(world) {
+                panic!("{e}");
+            }
+        })
+        .named(name);
+        if sandboxed {
+            spec.on_panic(OnPanic::Disable)
+        } else {
+            spec
+        }
+    }
+}
+
+/// An entity as seen by scripts: light userdata, so that it compares by value and can key
+/// tables.
+struct Entity(EntityID);
+impl IntoLua for Entity {
+    fn into_lua(self, _: &Lua) -> mlua::Result<Value> {
+        let bits = self.0.to_bits().get() as usize;
+        Ok(Value::LightUserData(LightUserData(
+            std::ptr::without_provenance_mut::<c_void>(bits),
+        )))
+    }
+}
+impl FromLua for Entity {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        match value {
+            Value::LightUserData(LightUserData(pointer)) => {
+                EntityID::from_bits(pointer.addr() as u64).map(Entity)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| {
+            mlua::Error::runtime(format!("Expected an entity, got {}.", value.type_name()))
+        })
+    }
+}
+
+/// The world lent to a script for one call.
+struct ScriptWorld<'w> {
+    world: &'w mut World,
+    registry: &'w Registry,
+}
+impl UserData for ScriptWorld<'_> {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("tick", |_, this| Ok(this.world.time().tick()));
+        fields.add_field_method_get("dt", |_, this| Ok(this.world.time().dt()));
+        fields.add_field_method_get("elapsed", |_, this| Ok(this.world.time().elapsed()));
+    }
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        // Iterates over copies of the components of every entity that has all of them.
+        methods.add_method("query", |lua, this, names: Variadic<String>| {
+            let types = names
+                .iter()
+                .map(|name| this.registry.get_component(name))
+                .collect::<mlua::Result<Vec<_>>>()?;
+            let Some((first, rest)) = types.split_first() else {
+                return Err(mlua::Error::runtime("Queries need at least one component."));
+            };
+            let mut rows = Vec::new();
+            for entity in first.entities(this.world) {
+                if !rest.iter().all(|t| t.has(this.world, entity)) {
+                    continue;
+                }
+                let mut row = MultiValue::new();
+                row.push_back(Entity(entity).into_lua(lua)?);
+                for t in &types {
+                    row.push_back(t.get(lua, this.world, entity)?.unwrap_or(Value::Nil));
+                }
+                rows.push(row);
+            }
+            let mut rows = rows.into_iter();
+            lua.create_function_mut(move |_, ()| Ok(rows.next().unwrap_or_default()))
+        });
+        methods.add_method("get", |lua, this, (entity, name): (Entity, String)| {
+            this.registry
+                .get_component(&name)?
+                .get(lua, this.world, entity.0)
+        });
+        methods.add_method_mut(
+            "set",
+            |lua, this, (entity, name, value): (Entity, String, Value)| {
+                this.registry
+                    .get_component(&name)?
+                    .set(lua, this.world, entity.0, value)
+            },
+        );
+        methods.add_method_mut("remove", |_, this, (entity, name): (Entity, String)| {
+            Ok(this
+                .registry
+                .get_component(&name)?
+                .remove(this.world, entity.0))
+        });
+        methods.add_method_mut("spawn", |lua, this, components: Option<Table>| {
+            let mut builder = hecs::EntityBuilder::new();
+            if let Some(components) = components {
+                for pair in components.pairs::<String, Value>() {
+                    let (name, value) = pair?;
+                    this.registry
+                        .get_component(&name)?
+                        .add(lua, &mut builder, value)?;
+                }
+            }
+            Ok(Entity(this.world.spawn(builder.build())))
+        });
+        methods.add_method_mut("despawn", |_, this, entity: Entity| {
+            Ok(this.world.despawn(entity.0).is_ok())
+        });
+        methods.add_method("resource", |lua, this, name: String| {
+            this.registry.get_resource(&name)?.get(lua, this.world)
+        });
+    }
+}
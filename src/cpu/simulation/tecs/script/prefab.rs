@@ -0,0 +1,165 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, bail};
+use mlua::{Lua, Table, Value};
+
+use crate::cpu::simulation::tecs::{
+    EntityID, World,
+    script::{file_name, maths, registry::Registry},
+};
+
+/// Named sets of component values, described by a Lua chunk returning a table of prefabs:
+///
+/// ```lua
+/// return {
+///     body = { Position = { x = 0, y = 0 }, Velocity = { x = 0, y = 0 } },
+///     ball = { extends = "body", Velocity = { x = 1 }, Radius = 0.5 },
+/// }
+/// ```
+///
+/// A prefab that `extends` another starts from its components. Plain tables are merged field
+/// by field, so `ball` above keeps the `y` of `body`'s velocity; any other value replaces the
+/// inherited one. Components are named by the `Registry`, and every prefab is checked against
+/// it when loading.
+pub struct Prefabs {
+    lua: Lua,
+    registry: Arc<Registry>,
+    /// Components of each prefab, including those it inherits.
+    prefabs: HashMap<String, Table>,
+}
+impl Prefabs {
+    pub fn new(name: &str, source: &str, registry: Arc<Registry>) -> anyhow::Result<Self> {
+        let lua = Lua::new();
+        maths::register(&lua)?;
+        let declared = lua
+            .load(source)
+            .set_name(name)
+            .eval::<HashMap<String, Table>>()
+            .map_err(|e| anyhow!("Prefabs {name} must be a table of prefab tables: {e}"))?;
+        let mut prefabs = Self {
+            lua,
+            registry,
+            prefabs: HashMap::new(),
+        };
+        let mut names = declared.keys().collect::<Vec<_>>();
+        names.sort();
+        for prefab in names {
+            prefabs.resolve(&declared, prefab, &mut Vec::new())?;
+        }
+        for prefab in prefabs.prefabs.keys() {
+            prefabs.builder(prefab)?;
+        }
+        Ok(prefabs)
+    }
+    /// Loads prefabs from a file.
+    pub fn load(path: &str, registry: Arc<Registry>) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)?;
+        Self::new(&file_name(path), &source, registry)
+    }
+    /// Merges the components of `prefab` over those of the prefabs it extends. `chain` holds
+    /// the prefabs being resolved, to catch cycles.
+    fn resolve(
+        &mut self,
+        declared: &HashMap<String, Table>,
+        prefab: &str,
+        chain: &mut Vec<String>,
+    ) -> anyhow::Result<Table> {
+        if let Some(resolved) = self.prefabs.get(prefab) {
+            return Ok(resolved.clone());
+        }
+        if let Some(start) = chain.iter().position(|p| p == prefab) {
+            let mut cycle = chain[start + 1..].to_vec();
+            cycle.push(prefab.to_string());
+            bail!(
+                "Prefab {prefab} extends itself through {}.",
+                cycle.join(" -> ")
+            );
+        }
+        let table = &declared[prefab];
+        let base = match table.raw_get::<Option<String>>("extends")? {
+            Some(base) if declared.contains_key(&base) => {
+                chain.push(prefab.to_string());
+                let base = self.resolve(declared, &base, chain)?;
+                chain.pop();
+                Some(base)
+            }
+            Some(base) => bail!("Prefab {prefab} extends unknown prefab {base}."),
+            None => None,
+        };
+        let own = self.lua.create_table()?;
+        for pair in table.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            if !matches!(&key, Value::String(key) if *key == "extends") {
+                own.raw_set(key, value)?;
+            }
+        }
+        let resolved = merge(&self.lua, base.as_ref(), &own)?;
+        self.prefabs.insert(prefab.to_string(), resolved.clone());
+        Ok(resolved)
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.prefabs.keys().map(String::as_str)
+    }
+    /// The components of `prefab`, ready to spawn.
+    pub fn builder(&self, prefab: &str) -> anyhow::Result<hecs::EntityBuilder> {
+        let table = self
+            .prefabs
+            .get(prefab)
+            .ok_or_else(|| anyhow!("Prefab {prefab} does not exist."))?;
+        let mut builder = hecs::EntityBuilder::new();
+        for pair in table.pairs::<String, Value>() {
+            let (component, value) = pair?;
+            self.registry
+                .get_component(&component)
+                .and_then(|c| c.add(&self.lua, &mut builder, value))
+                .map_err(|e| anyhow!("Prefab {prefab} has an invalid {component}: {e}"))?;
+        }
+        Ok(builder)
+    }
+    /// Spawns an instance of `prefab` with `overrides` added on top of its components.
+    pub fn spawn(
+        &self,
+        world: &mut World,
+        prefab: &str,
+        overrides: impl hecs::DynamicBundle,
+    ) -> anyhow::Result<EntityID> {
+        let mut builder = self.builder(prefab)?;
+        builder.add_bundle(overrides);
+        Ok(world.spawn(builder.build()))
+    }
+}
+
+/// Whether a table is plain data rather than an object with a metatable.
+fn plain(table: &Table) -> bool {
+    table.metatable().is_none()
+}
+
+/// A copy of `base` with `overrides` merged into it, recursing into plain tables.
+fn merge(lua: &Lua, base: Option<&Table>, overrides: &Table) -> mlua::Result<Table> {
+    let merged = lua.create_table()?;
+    if let Some(base) = base {
+        for pair in base.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            merged.raw_set(key, copy(lua, value)?)?;
+        }
+    }
+    for pair in overrides.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        let value = match (merged.raw_get::<Value>(&key)?, value) {
+            (Value::Table(base), Value::Table(value)) if plain(&base) && plain(&value) => {
+                Value::Table(merge(lua, Some(&base), &value)?)
+            }
+            (_, value) => copy(lua, value)?,
+        };
+        merged.raw_set(key, value)?;
+    }
+    Ok(merged)
+}
+
+/// Deep-copies plain tables, so that merging never changes a base prefab.
+fn copy(lua: &Lua, value: Value) -> mlua::Result<Value> {
+    match value {
+        Value::Table(table) if plain(&table) => Ok(Value::Table(merge(lua, None, &table)?)),
+        value => Ok(value),
+    }
+}
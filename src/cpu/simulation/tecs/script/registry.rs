@@ -0,0 +1,121 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use mlua::{FromLua, IntoLua, Lua, Value};
+
+use crate::cpu::simulation::tecs::{EntityID, World};
+
+/// Component type reached through Lua values.
+pub(crate) trait ComponentType: Send + Sync {
+    fn has(&self, world: &World, entity: EntityID) -> bool;
+    fn entities(&self, world: &World) -> Vec<EntityID>;
+    fn get(&self, lua: &Lua, world: &World, entity: EntityID) -> mlua::Result<Option<Value>>;
+    /// Adds or replaces the component. Fails if `entity` doesn't exist.
+    fn set(&self, lua: &Lua, world: &mut World, entity: EntityID, value: Value)
+    -> mlua::Result<()>;
+    fn remove(&self, world: &mut World, entity: EntityID) -> bool;
+    fn add(&self, lua: &Lua, builder: &mut hecs::EntityBuilder, value: Value) -> mlua::Result<()>;
+}
+
+/// Resource type reached through Lua values.
+pub(crate) trait ResourceType: Send + Sync {
+    fn get(&self, lua: &Lua, world: &World) -> mlua::Result<Option<Value>>;
+}
+
+struct Typed<T>(PhantomData<fn() -> T>);
+impl<T: hecs::Component + Clone + IntoLua + FromLua> ComponentType for Typed<T> {
+    fn has(&self, world: &World, entity: EntityID) -> bool {
+        world
+            .hecs_world
+            .entity(entity)
+            .is_ok_and(|entity| entity.has::<T>())
+    }
+    fn entities(&self, world: &World) -> Vec<EntityID> {
+        world
+            .hecs_world
+            .query::<EntityID>()
+            .with::<&T>()
+            .iter()
+            .collect()
+    }
+    fn get(&self, lua: &Lua, world: &World, entity: EntityID) -> mlua::Result<Option<Value>> {
+        match world.hecs_world.get::<&T>(entity) {
+            Ok(component) => Ok(Some(T::clone(&component).into_lua(lua)?)),
+            Err(_) => Ok(None),
+        }
+    }
+    fn set(
+        &self,
+        lua: &Lua,
+        world: &mut World,
+        entity: EntityID,
+        value: Value,
+    ) -> mlua::Result<()> {
+        let component = T::from_lua(value, lua)?;
+        world
+            .hecs_world
+            .insert_one(entity, component)
+            .map_err(|_| mlua::Error::runtime(format!("Entity {entity:?} does not exist.")))
+    }
+    fn remove(&self, world: &mut World, entity: EntityID) -> bool {
+        world.hecs_world.remove_one::<T>(entity).is_ok()
+    }
+    fn add(&self, lua: &Lua, builder: &mut hecs::EntityBuilder, value: Value) -> mlua::Result<()> {
+        builder.add(T::from_lua(value, lua)?);
+        Ok(())
+    }
+}
+impl<T: Clone + IntoLua + Send + Sync + 'static> ResourceType for Typed<T> {
+    fn get(&self, lua: &Lua, world: &World) -> mlua::Result<Option<Value>> {
+        match world.resource::<T>() {
+            Some(resource) => Ok(Some(T::clone(&resource).into_lua(lua)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Component and resource types scripts can use, by name. Values are copied into and out of
+/// scripts through their `IntoLua` and `FromLua` conversions.
+#[derive(Default)]
+pub struct Registry {
+    components: HashMap<String, Box<dyn ComponentType>>,
+    resources: HashMap<String, Box<dyn ResourceType>>,
+}
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Exposes components of type `T` to scripts as `name`.
+    pub fn component<T: hecs::Component + Clone + IntoLua + FromLua>(
+        mut self,
+        name: impl Into<String>,
+    ) -> Self {
+        self.components
+            .insert(name.into(), Box::new(Typed::<T>(PhantomData)));
+        self
+    }
+    /// Lets scripts read the world's `T` as `name`.
+    pub fn resource<T: Clone + IntoLua + Send + Sync + 'static>(
+        mut self,
+        name: impl Into<String>,
+    ) -> Self {
+        self.resources
+            .insert(name.into(), Box::new(Typed::<T>(PhantomData)));
+        self
+    }
+    pub(crate) fn get_component(&self, name: &str) -> mlua::Result<&dyn ComponentType> {
+        match self.components.get(name) {
+            Some(component) => Ok(component.as_ref()),
+            None => Err(mlua::Error::runtime(format!(
+                "Component {name} is not registered."
+            ))),
+        }
+    }
+    pub(crate) fn get_resource(&self, name: &str) -> mlua::Result<&dyn ResourceType> {
+        match self.resources.get(name) {
+            Some(resource) => Ok(resource.as_ref()),
+            None => Err(mlua::Error::runtime(format!(
+                "Resource {name} is not registered."
+            ))),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use mlua::{Lua, LuaOptions, StdLib, VmState};
+
+use crate::cpu::simulation::tecs::script::maths;
+
+/// Limits on a sandboxed script. Budgets apply afresh to loading the script and to each run of
+/// its system function.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Limits {
+    /// Luau interrupts, which fire on calls and loop iterations: a rough instruction budget.
+    pub interrupts: Option<u64>,
+    /// Wall-clock time.
+    pub time: Option<Duration>,
+    /// Bytes the script's Lua state may hold at once, standard library included.
+    pub memory: Option<usize>,
+}
+
+#[derive(Default)]
+struct Usage {
+    interrupts: u64,
+    deadline: Option<Instant>,
+}
+
+/// Budgets of a sandboxed Lua state.
+#[derive(Clone)]
+pub(crate) struct Sandbox {
+    limits: Limits,
+    usage: Arc<Mutex<Usage>>,
+}
+impl Sandbox {
+    /// Creates a Lua state without `os`, `debug` or `require`, whose globals are read-only and
+    /// which enforces `limits`.
+    pub(crate) fn new(limits: Limits) -> mlua::Result<(Lua, Self)> {
+        let libs = StdLib::COROUTINE
+            | StdLib::TABLE
+            | StdLib::STRING
+            | StdLib::UTF8
+            | StdLib::BIT
+            | StdLib::MATH
+            | StdLib::BUFFER
+            | StdLib::VECTOR;
+        let lua = Lua::new_with(libs, LuaOptions::default())?;
+        lua.globals().raw_remove("require")?;
+        maths::register(&lua)?;
+        lua.sandbox(true)?;
+        if let Some(memory) = limits.memory {
+            lua.set_memory_limit(memory)?;
+        }
+        let sandbox = Self {
+            limits,
+            usage: Arc::default(),
+        };
+        let usage = sandbox.usage.clone();
+        lua.set_interrupt(move |_| {
+            let mut usage = usage.lock().unwrap_or_else(|e| e.into_inner());
+            usage.interrupts += 1;
+            if let Some(budget) = limits.interrupts
+                && usage.interrupts > budget
+            {
+                return Err(mlua::Error::runtime(format!(
+                    "Script exceeded its budget of {budget} interrupts."
+                )));
+            }
+            if let (Some(budget), Some(deadline)) = (limits.time, usage.deadline)
+                && Instant::now() > deadline
+            {
+                return Err(mlua::Error::runtime(format!(
+                    "Script exceeded its time budget of {budget:?}."
+                )));
+            }
+            Ok(VmState::Continue)
+        });
+        Ok((lua, sandbox))
+    }
+    /// Resets the budgets for a run starting now.
+    pub(crate) fn start(&self) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.interrupts = 0;
+        usage.deadline = self.limits.time.map(|time| Instant::now() + time);
+    }
+}
@@ -0,0 +1,220 @@
+use std::any::{TypeId, type_name_of_val};
+
+use hecs::QueryBorrow;
+
+use crate::cpu::simulation::tecs::{
+    EntityID, Query, System, World, event::EventQueue, fault::OnPanic, schedule::Stage,
+};
+
+/// Components and resources a system reads and writes. Systems whose accesses don't conflict
+/// may run in parallel on a shared `&World`.
+#[derive(Clone, Default, Debug)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+    exclusive: bool,
+}
+impl Access {
+    /// Conflicts with every other system, as needed by systems taking `&mut World`.
+    pub fn exclusive() -> Self {
+        Self {
+            exclusive: true,
+            ..Self::default()
+        }
+    }
+    pub fn read<T: 'static>(mut self) -> Self {
+        self.reads.push(TypeId::of::<T>());
+        self
+    }
+    pub fn write<T: 'static>(mut self) -> Self {
+        self.writes.push(TypeId::of::<T>());
+        self
+    }
+    /// Sending events of type `E`, which orders the system against others using them.
+    pub fn send_event<E: 'static>(self) -> Self {
+        self.write::<EventQueue<E>>()
+    }
+    pub fn read_events<E: 'static>(self) -> Self {
+        self.read::<EventQueue<E>>()
+    }
+    /// The components borrowed by the query `Q`.
+    pub fn of<Q: QueryAccess>() -> Self {
+        let mut access = Self::default();
+        Q::access(&mut access);
+        access
+    }
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+    /// Whether the system may write `component`, which always holds for exclusive systems.
+    pub(crate) fn may_write(&self, component: TypeId) -> bool {
+        self.exclusive || self.writes.contains(&component)
+    }
+    /// Whether one side writes something the other reads or writes.
+    pub fn conflicts(&self, other: &Access) -> bool {
+        let overlaps = |a: &[TypeId], b: &[TypeId]| a.iter().any(|t| b.contains(t));
+        self.exclusive
+            || other.exclusive
+            || overlaps(&self.writes, &other.writes)
+            || overlaps(&self.writes, &other.reads)
+            || overlaps(&self.reads, &other.writes)
+    }
+}
+
+/// Queries whose component borrows are known from their type.
+pub trait QueryAccess {
+    fn access(access: &mut Access);
+}
+impl QueryAccess for EntityID {
+    fn access(_: &mut Access) {}
+}
+impl<T: 'static> QueryAccess for &T {
+    fn access(access: &mut Access) {
+        access.reads.push(TypeId::of::<T>());
+    }
+}
+impl<T: 'static> QueryAccess for &mut T {
+    fn access(access: &mut Access) {
+        access.writes.push(TypeId::of::<T>());
+    }
+}
+impl<Q: QueryAccess> QueryAccess for Option<Q> {
+    fn access(access: &mut Access) {
+        Q::access(access);
+    }
+}
+impl<Q: QueryAccess, R> QueryAccess for hecs::With<Q, R> {
+    fn access(access: &mut Access) {
+        Q::access(access);
+    }
+}
+impl<Q: QueryAccess, R> QueryAccess for hecs::Without<Q, R> {
+    fn access(access: &mut Access) {
+        Q::access(access);
+    }
+}
+macro_rules! impl_query_access {
+    ($($name:ident),*) => {
+        impl<$($name: QueryAccess),*> QueryAccess for ($($name,)*) {
+            fn access(_access: &mut Access) {
+                $($name::access(_access);)*
+            }
+        }
+    };
+}
+impl_query_access!();
+impl_query_access!(A);
+impl_query_access!(A, B);
+impl_query_access!(A, B, C);
+impl_query_access!(A, B, C, D);
+impl_query_access!(A, B, C, D, E);
+impl_query_access!(A, B, C, D, E, F);
+impl_query_access!(A, B, C, D, E, F, G);
+impl_query_access!(A, B, C, D, E, F, G, H);
+
+pub(crate) enum Run {
+    Exclusive(Box<dyn System>),
+    Shared(Box<dyn FnMut(&World) + Send>),
+}
+impl Run {
+    /// Runs a shared system. Exclusive systems never share a batch, so they are not run here.
+    pub(crate) fn shared(&mut self, world: &World) {
+        if let Run::Shared(system) = self {
+            system(world);
+        }
+    }
+}
+
+/// A system together with the access it declares and where it goes in the schedule.
+pub struct SystemSpec {
+    pub(crate) name: String,
+    /// Whether the name was given with `named` rather than defaulted.
+    pub(crate) named: bool,
+    pub(crate) stage: Stage,
+    pub(crate) before: Vec<String>,
+    pub(crate) after: Vec<String>,
+    pub(crate) access: Access,
+    pub(crate) run: Run,
+    pub(crate) on_panic: Option<OnPanic>,
+    pub(crate) disabled: bool,
+    /// Change tick at the end of the system's last run.
+    pub(crate) last_run: u64,
+}
+impl SystemSpec {
+    fn new(name: &str, access: Access, run: Run) -> Self {
+        Self {
+            name: name.to_string(),
+            named: false,
+            stage: Stage::default(),
+            before: Vec::new(),
+            after: Vec::new(),
+            access,
+            run,
+            on_panic: None,
+            disabled: false,
+            last_run: 0,
+        }
+    }
+    /// Runs alone, with mutable access to the whole world.
+    pub fn exclusive(system: impl System + 'static) -> Self {
+        let name = type_name_of_val(&system);
+        Self::new(name, Access::exclusive(), Run::Exclusive(Box::new(system)))
+    }
+    /// Runs alongside systems that don't conflict with `access`. Queries outside `access`
+    /// panic if they collide with a system running at the same time.
+    pub fn shared(access: Access, system: impl FnMut(&World) + Send + 'static) -> Self {
+        Self::new(
+            type_name_of_val(&system),
+            access,
+            Run::Shared(Box::new(system)),
+        )
+    }
+    /// Runs `system` over the query `Q`, with the access inferred from `Q`.
+    pub fn query<Q: Query + QueryAccess>(
+        mut system: impl FnMut(QueryBorrow<'_, Q>) + Send + 'static,
+    ) -> Self {
+        Self::new(
+            type_name_of_val(&system),
+            Access::of::<Q>(),
+            Run::Shared(Box::new(move |world: &World| system(world.query::<Q>()))),
+        )
+    }
+    /// Names the system for ordering constraints and error reports. Only named systems can be
+    /// the target of `before` and `after`. The default name is the system's type name followed
+    /// by its position in the schedule, as closures defined in one function share a type name.
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self.named = true;
+        self
+    }
+    pub fn in_stage(mut self, stage: Stage) -> Self {
+        self.stage = stage;
+        self
+    }
+    /// Runs before every system named `name`.
+    pub fn before(mut self, name: impl Into<String>) -> Self {
+        self.before.push(name.into());
+        self
+    }
+    /// Runs after every system named `name`.
+    pub fn after(mut self, name: impl Into<String>) -> Self {
+        self.after.push(name.into());
+        self
+    }
+    /// Overrides `Settings::on_panic` for this system.
+    pub fn on_panic(mut self, on_panic: OnPanic) -> Self {
+        self.on_panic = Some(on_panic);
+        self
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+}
+impl<F: System + 'static> From<F> for SystemSpec {
+    fn from(system: F) -> Self {
+        Self::exclusive(system)
+    }
+}
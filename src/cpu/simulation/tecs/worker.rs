@@ -0,0 +1,152 @@
+use std::{
+    ops::ControlFlow,
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, RecvTimeoutError},
+    },
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::cpu::simulation::tecs::{
+    Message, Settings, World,
+    clock::{Clock, Mode},
+    fault::{OnPanic, SystemPanic, panic_message},
+    schedule::Schedule,
+};
+
+/// State owned by the simulation thread.
+pub(crate) struct Worker {
+    world: World,
+    schedule: Schedule,
+    clock: Clock,
+    on_panic: OnPanic,
+    /// The panic that halted the simulation, under `OnPanic::Halt`.
+    halted: Option<SystemPanic>,
+    faults: Vec<SystemPanic>,
+}
+impl Worker {
+    fn new(settings: Settings) -> Self {
+        Self {
+            world: World::new(settings.timestep),
+            schedule: Schedule::default(),
+            clock: Clock::default(),
+            on_panic: settings.on_panic,
+            halted: None,
+            faults: Vec::new(),
+        }
+    }
+    /// Handles messages until shut down or until the handle is gone. Fails with the panic that
+    /// halted the simulation, or with a panic outside systems, which also ends the thread and
+    /// is recorded in `stopped` for the handle to report.
+    pub(crate) fn run(
+        rx: Receiver<Message>,
+        settings: Settings,
+        stopped: Arc<Mutex<Option<String>>>,
+    ) -> Result<()> {
+        let mut worker = Self::new(settings);
+        loop {
+            let message = match worker.clock.wait(worker.world.time().dt()) {
+                None => match rx.recv() {
+                    Ok(message) => Some(message),
+                    Err(_) => break,
+                },
+                Some(timeout) => match rx.recv_timeout(timeout) {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+            };
+            let flow = catch_unwind(AssertUnwindSafe(|| match message {
+                Some(message) => worker.handle(message),
+                None => {
+                    worker.background_tick();
+                    ControlFlow::Continue(())
+                }
+            }));
+            match flow {
+                Ok(ControlFlow::Continue(())) => {}
+                Ok(ControlFlow::Break(())) => break,
+                Err(payload) => {
+                    let cause = panic_message(&*payload);
+                    *stopped.lock().unwrap_or_else(|e| e.into_inner()) = Some(cause.clone());
+                    return Err(anyhow!("Simulation thread panicked: {cause}"));
+                }
+            }
+        }
+        match worker.halted {
+            Some(fault) => Err(fault.into()),
+            None => Ok(()),
+        }
+    }
+    fn handle(&mut self, message: Message) -> ControlFlow<()> {
+        match message {
+            Message::Tick { ticks, done } => {
+                let mut result = Ok(());
+                for _ in 0..ticks {
+                    if let Err(fault) = self.tick() {
+                        result = result.and(Err(fault));
+                        if self.halted.is_some() {
+                            break;
+                        }
+                    }
+                }
+                let _ = done.send(result.map(|()| self.world.time().tick()));
+            }
+            Message::Run => self.clock.start(Mode::Continuous),
+            Message::RunFor(0) => {}
+            Message::RunFor(n) => self.clock.start(Mode::For(n)),
+            Message::RunUntil(predicate) => self.clock.start(Mode::Until(predicate)),
+            Message::Pause => self.clock.paused = true,
+            Message::Resume => self.clock.paused = false,
+            Message::Pacing(pacing) => self.clock.pacing = pacing,
+            Message::Apply(apply) => apply(&mut self.world),
+            Message::Create { spawn_fn, response } => {
+                let e = spawn_fn(&mut self.world);
+                response.send(e).unwrap();
+            }
+            Message::Delete { entity } => self
+                .world
+                .despawn(entity)
+                .expect("Could not despawn entity."),
+            Message::Systems { systems, response } => {
+                let _ = response.send(self.schedule.add_systems(systems));
+            }
+            Message::Faults { response } => {
+                let _ = response.send(self.faults.clone());
+            }
+            Message::Shutdown => return ControlFlow::Break(()),
+        }
+        ControlFlow::Continue(())
+    }
+    /// Runs a tick, unless a panic has halted the simulation. Fails with the first panic.
+    fn tick(&mut self) -> Result<(), SystemPanic> {
+        if let Some(fault) = &self.halted {
+            return Err(fault.clone());
+        }
+        let recorded = self.faults.len();
+        match self
+            .world
+            .tick(&mut self.schedule, self.on_panic, &mut self.faults)
+        {
+            Ok(()) => match self.faults.get(recorded) {
+                Some(fault) => Err(fault.clone()),
+                None => Ok(()),
+            },
+            Err(fault) => {
+                self.faults.push(fault.clone());
+                self.halted = Some(fault.clone());
+                Err(fault)
+            }
+        }
+    }
+    /// Runs a tick of the current run mode. A halted simulation stops running.
+    fn background_tick(&mut self) {
+        let _ = self.tick();
+        match self.halted {
+            Some(_) => self.clock.stop(),
+            None => self.clock.ticked(&self.world),
+        }
+    }
+}
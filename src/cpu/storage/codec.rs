@@ -0,0 +1,99 @@
+use std::borrow::Cow;
+
+use bytemuck::Pod;
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Converts keys and values to and from the bytes persisted by `Storage`.
+pub trait Codec<T>: Send + 'static {
+    fn encode(value: &T) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> anyhow::Result<T>;
+}
+
+/// Fixed-size raw byte copy of a `Pod` value.
+pub struct PodCodec;
+impl<T: Pod> Codec<T> for PodCodec {
+    fn encode(value: &T) -> Vec<u8> {
+        bytemuck::bytes_of(value).to_vec()
+    }
+    fn decode(bytes: &[u8]) -> anyhow::Result<T> {
+        anyhow::ensure!(
+            bytes.len() == std::mem::size_of::<T>(),
+            "Expected {} bytes for {}, found {}.",
+            std::mem::size_of::<T>(),
+            std::any::type_name::<T>(),
+            bytes.len()
+        );
+        Ok(bytemuck::pod_read_unaligned(bytes))
+    }
+}
+
+/// Variable-length run of `Pod` values, stored back to back.
+pub struct PodSliceCodec;
+impl PodSliceCodec {
+    /// Borrows `bytes` as a slice of `T` when the alignment allows it, copying only otherwise.
+    pub fn view<T: Pod>(bytes: &[u8]) -> anyhow::Result<Cow<'_, [T]>> {
+        let size = std::mem::size_of::<T>();
+        anyhow::ensure!(
+            bytes.len().is_multiple_of(size),
+            "{} bytes is not a whole number of {}.",
+            bytes.len(),
+            std::any::type_name::<T>()
+        );
+        Ok(match bytemuck::try_cast_slice(bytes) {
+            Ok(slice) => Cow::Borrowed(slice),
+            Err(_) => Cow::Owned(bytemuck::pod_collect_to_vec(bytes)),
+        })
+    }
+}
+impl<T: Pod + Send> Codec<Vec<T>> for PodSliceCodec {
+    fn encode(value: &Vec<T>) -> Vec<u8> {
+        bytemuck::cast_slice(value).to_vec()
+    }
+    fn decode(bytes: &[u8]) -> anyhow::Result<Vec<T>> {
+        Ok(Self::view(bytes)?.into_owned())
+    }
+}
+
+/// Big-endian integers, with the sign bit flipped for signed types, so that the encoded
+/// bytes sort in numeric order. Use it for keys that are queried by range.
+pub struct OrderedCodec;
+macro_rules! impl_ordered_codec {
+    ($($t:ty => $flip:expr),*) => {
+        $(
+            impl Codec<$t> for OrderedCodec {
+                fn encode(value: &$t) -> Vec<u8> {
+                    (value ^ $flip).to_be_bytes().to_vec()
+                }
+                fn decode(bytes: &[u8]) -> anyhow::Result<$t> {
+                    Ok(<$t>::from_be_bytes(bytes.try_into()?) ^ $flip)
+                }
+            }
+        )*
+    };
+}
+impl_ordered_codec!(
+    u8 => 0, u16 => 0, u32 => 0, u64 => 0,
+    i8 => i8::MIN, i16 => i16::MIN, i32 => i32::MIN, i64 => i64::MIN
+);
+
+/// Raw UTF-8, so that string keys keep their natural order and can be watched by prefix.
+pub struct Utf8Codec;
+impl Codec<String> for Utf8Codec {
+    fn encode(value: &String) -> Vec<u8> {
+        value.as_bytes().to_vec()
+    }
+    fn decode(bytes: &[u8]) -> anyhow::Result<String> {
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+}
+
+/// Self-describing JSON encoding for anything `serde` can handle.
+pub struct JsonCodec;
+impl<T: Serialize + DeserializeOwned> Codec<T> for JsonCodec {
+    fn encode(value: &T) -> Vec<u8> {
+        serde_json::to_vec(value).expect("JSON encoding failed.")
+    }
+    fn decode(bytes: &[u8]) -> anyhow::Result<T> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
@@ -0,0 +1,556 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    ops::RangeBounds,
+};
+
+use bytemuck::Pod;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::cpu::storage::{Storage, codec::Codec};
+
+/// Rows sent to the worker per write transaction when importing.
+const IMPORT_BATCH: usize = 1024;
+
+/// Scalar types with a NumPy dtype. Data is written in the host's byte order, which is
+/// little-endian on every platform wgpu targets.
+pub trait NpyElement: Pod + Send {
+    const DESCR: &'static str;
+}
+macro_rules! impl_npy_element {
+    ($($t:ty => $descr:literal),*) => {
+        $(
+            impl NpyElement for $t {
+                const DESCR: &'static str = $descr;
+            }
+        )*
+    };
+}
+impl_npy_element!(
+    u8 => "|u1", i8 => "|i1", u16 => "<u2", i16 => "<i2", u32 => "<u4", i32 => "<i4",
+    u64 => "<u8", i64 => "<i8", f32 => "<f4", f64 => "<f8"
+);
+
+/// Builds a version 1.0 `.npy` header, padded to 64 bytes or to exactly `len` if given.
+fn npy_header(descr: &str, shape: &[usize], len: Option<usize>) -> Vec<u8> {
+    let dims = shape.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+    let dims = match dims.as_slice() {
+        [d] => format!("({d},)"),
+        _ => format!("({})", dims.join(", ")),
+    };
+    let dict = format!("{{'descr': '{descr}', 'fortran_order': False, 'shape': {dims}, }}");
+    let total = len.unwrap_or((10 + dict.len() + 1).div_ceil(64) * 64);
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&((total - 10) as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header.resize(total - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+/// Streams rows into a `.npy` file whose leading dimension is only known at the end.
+struct NpyWriter<W: Write + Seek> {
+    inner: W,
+    descr: &'static str,
+    shape: Vec<usize>,
+    rows: usize,
+    header_len: usize,
+}
+impl<W: Write + Seek> NpyWriter<W> {
+    fn new(mut inner: W, descr: &'static str, shape: &[usize]) -> anyhow::Result<Self> {
+        // Reserve room for the widest possible row count, then patch it in `finish`.
+        let widest = [&[usize::MAX], shape].concat();
+        let header = npy_header(descr, &widest, None);
+        inner.write_all(&header)?;
+        Ok(Self {
+            inner,
+            descr,
+            shape: shape.to_vec(),
+            rows: 0,
+            header_len: header.len(),
+        })
+    }
+    fn push(&mut self, row: &[u8]) -> anyhow::Result<()> {
+        self.inner.write_all(row)?;
+        self.rows += 1;
+        Ok(())
+    }
+    fn finish(mut self) -> anyhow::Result<W> {
+        let shape = [&[self.rows], self.shape.as_slice()].concat();
+        self.inner.seek(SeekFrom::Start(0))?;
+        self.inner
+            .write_all(&npy_header(self.descr, &shape, Some(self.header_len)))?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Reads a `.npy` array of `E`, returning its shape and raw little-endian data.
+fn read_npy<E: NpyElement>(mut reader: impl Read) -> anyhow::Result<(Vec<usize>, Vec<u8>)> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    anyhow::ensure!(&magic[..6] == b"\x93NUMPY", "Not a .npy file.");
+    let header_len = if magic[6] == 1 {
+        let mut len = [0u8; 2];
+        reader.read_exact(&mut len)?;
+        u16::from_le_bytes(len) as usize
+    } else {
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        u32::from_le_bytes(len) as usize
+    };
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8(header)?;
+    let field = |name: &str| {
+        header
+            .split_once(&format!("'{name}':"))
+            .map(|(_, rest)| rest.trim_start())
+            .ok_or_else(|| anyhow::anyhow!("The .npy header has no '{name}'."))
+    };
+    let descr = field("descr")?.trim_start_matches('\'');
+    anyhow::ensure!(
+        descr.starts_with(E::DESCR),
+        "Expected dtype {} but the .npy file holds {}.",
+        E::DESCR,
+        descr.split('\'').next().unwrap_or_default()
+    );
+    anyhow::ensure!(
+        field("fortran_order")?.starts_with("False"),
+        "Fortran-ordered .npy files are not supported."
+    );
+    let dims = field("shape")?;
+    let dims = &dims[1..dims.find(')').unwrap_or(1)];
+    let shape = dims
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<usize>, _>>()?;
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    anyhow::ensure!(
+        data.len() == shape.iter().product::<usize>() * std::mem::size_of::<E>(),
+        "The .npy data does not match its shape {shape:?}."
+    );
+    Ok((shape, data))
+}
+
+/// Checks that a `V` is exactly `shape` elements of `E`.
+pub(crate) fn check_shape<V, E>(shape: &[usize]) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        std::mem::size_of::<V>() == shape.iter().product::<usize>() * std::mem::size_of::<E>(),
+        "{} is not a {shape:?} array of {}.",
+        std::any::type_name::<V>(),
+        std::any::type_name::<E>()
+    );
+    Ok(())
+}
+
+/// Stores named `.npy` arrays uncompressed in a `.npz` archive, as `numpy.savez` does.
+pub(crate) fn write_npz(path: &str, arrays: Vec<(&str, Vec<u8>)>) -> anyhow::Result<()> {
+    let mut zip = zip::ZipWriter::new(File::create(path)?);
+    for (name, npy) in arrays {
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .large_file(npy.len() as u64 >= u32::MAX as u64);
+        zip.start_file(name, options)?;
+        zip.write_all(&npy)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+pub(crate) fn npy_buffer(descr: &'static str, shape: &[usize]) -> anyhow::Result<NpyBuffer> {
+    Ok(NpyBuffer(NpyWriter::new(
+        Cursor::new(Vec::new()),
+        descr,
+        shape,
+    )?))
+}
+
+/// An in-memory `.npy` array, for members of a `.npz` archive.
+pub(crate) struct NpyBuffer(NpyWriter<Cursor<Vec<u8>>>);
+impl NpyBuffer {
+    pub(crate) fn push(&mut self, row: &[u8]) -> anyhow::Result<()> {
+        self.0.push(row)
+    }
+    pub(crate) fn rows(&self) -> usize {
+        self.0.rows
+    }
+    pub(crate) fn finish(self) -> anyhow::Result<Vec<u8>> {
+        Ok(self.0.finish()?.into_inner())
+    }
+}
+
+/// Reads the array `name` out of a `.npz` archive.
+pub(crate) fn read_npz<E: NpyElement>(
+    path: &str,
+    name: &str,
+) -> anyhow::Result<(Vec<usize>, Vec<u8>)> {
+    read_npy::<E>(zip::ZipArchive::new(File::open(path)?)?.by_name(name)?)
+}
+
+/// Quotes a CSV cell when it needs it.
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Flattens `value` into `(column, cell)` pairs, naming nested fields and array items with
+/// dotted paths. Strings are always quoted so that they read back as strings.
+fn flatten(path: String, value: &Value, row: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                flatten(format!("{path}.{name}"), field, row);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten(format!("{path}.{i}"), item, row);
+            }
+        }
+        Value::String(s) => row.push((path, format!("\"{}\"", s.replace('"', "\"\"")))),
+        Value::Null => row.push((path, String::new())),
+        other => row.push((path, other.to_string())),
+    }
+}
+
+/// Splits CSV text into records of `(cell, was_quoted)`.
+fn parse_csv(text: &str) -> anyhow::Result<Vec<Vec<(String, bool)>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if cell.is_empty() && !quoted => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            cell.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => cell.push(c),
+                        None => anyhow::bail!("Unterminated quoted CSV cell."),
+                    }
+                }
+            }
+            ',' => record.push((std::mem::take(&mut cell), std::mem::take(&mut quoted))),
+            '\r' => {}
+            '\n' => {
+                record.push((std::mem::take(&mut cell), std::mem::take(&mut quoted)));
+                records.push(std::mem::take(&mut record));
+            }
+            c => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || quoted || !record.is_empty() {
+        record.push((cell, quoted));
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Places `leaf` at a dotted path, creating arrays for numeric segments and objects otherwise.
+fn unflatten(node: &mut Value, path: &[&str], leaf: Value) {
+    let Some((segment, rest)) = path.split_first() else {
+        *node = leaf;
+        return;
+    };
+    let child = match segment.parse::<usize>() {
+        Ok(i) => {
+            if !node.is_array() {
+                *node = Value::Array(Vec::new());
+            }
+            let items = node.as_array_mut().unwrap();
+            if items.len() <= i {
+                items.resize(i + 1, Value::Null);
+            }
+            &mut items[i]
+        }
+        Err(_) => {
+            if !node.is_object() {
+                *node = Value::Object(Default::default());
+            }
+            node.as_object_mut()
+                .unwrap()
+                .entry(segment.to_string())
+                .or_insert(Value::Null)
+        }
+    };
+    unflatten(child, rest, leaf);
+}
+
+#[derive(Serialize)]
+struct Record<'a, K, V> {
+    key: &'a K,
+    value: &'a V,
+}
+#[derive(Deserialize)]
+struct OwnedRecord<K, V> {
+    key: K,
+    value: V,
+}
+
+impl<K, V, KC, VC> Storage<K, V, KC, VC>
+where
+    K: Clone + Send + 'static,
+    V: Send + 'static,
+    KC: Codec<K>,
+    VC: Codec<V>,
+{
+    /// Like `scan`, but visits a read snapshot on a thread of its own, as `backup` does, so that
+    /// writing an export out never holds up the worker.
+    pub(crate) async fn scan_detached<S: Send + 'static>(
+        &self,
+        range: impl RangeBounds<K>,
+        state: S,
+        visit: impl FnMut(&mut S, K, V) -> anyhow::Result<()> + Send + 'static,
+    ) -> anyhow::Result<S> {
+        let snapshot = self.read_snapshot().await?;
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        std::thread::spawn(move || {
+            let _ = resp_tx.send(snapshot.scan(range, state, visit));
+        });
+        resp_rx.await?
+    }
+    /// Streams `range` to a CSV file with `key` and `value` columns, nested fields flattened
+    /// into dotted column names in field order. Every record must flatten to the same columns.
+    pub async fn export_csv(&self, path: &str, range: impl RangeBounds<K>) -> anyhow::Result<usize>
+    where
+        K: Serialize,
+        V: Serialize,
+    {
+        let writer = BufWriter::new(File::create(path)?);
+        let (mut writer, _, rows) = self
+            .scan_detached(
+                range,
+                (writer, None::<Vec<String>>, 0),
+                |(writer, columns, rows), key, value| {
+                    let mut row = Vec::new();
+                    flatten("key".into(), &serde_json::to_value(&key)?, &mut row);
+                    flatten("value".into(), &serde_json::to_value(&value)?, &mut row);
+                    let columns = match columns {
+                        Some(columns) => columns,
+                        None => {
+                            let names = row.iter().map(|(name, _)| csv_escape(name));
+                            writeln!(writer, "{}", names.collect::<Vec<_>>().join(","))?;
+                            columns.insert(row.iter().map(|(name, _)| name.clone()).collect())
+                        }
+                    };
+                    anyhow::ensure!(
+                        row.iter().map(|(name, _)| name).eq(columns.iter()),
+                        "Record {rows} does not have the same columns as the first record."
+                    );
+                    let cells = row.into_iter().map(|(_, cell)| cell);
+                    writeln!(writer, "{}", cells.collect::<Vec<_>>().join(","))?;
+                    *rows += 1;
+                    Ok(())
+                },
+            )
+            .await?;
+        writer.flush()?;
+        Ok(rows)
+    }
+    /// Loads a CSV file written by `export_csv`. Quoted cells are read as strings, empty
+    /// ones as null and anything else as a JSON number or boolean.
+    pub async fn import_csv(&self, path: &str) -> anyhow::Result<usize>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        let mut records = parse_csv(&std::fs::read_to_string(path)?)?.into_iter();
+        let Some(header) = records.next() else {
+            return Ok(0);
+        };
+        let mut batch = Vec::with_capacity(IMPORT_BATCH);
+        let mut count = 0;
+        for (i, cells) in records.enumerate() {
+            anyhow::ensure!(
+                cells.len() == header.len(),
+                "CSV row {} has {} cells but the header has {}.",
+                i + 1,
+                cells.len(),
+                header.len()
+            );
+            let mut record = Value::Null;
+            for ((name, _), (cell, quoted)) in header.iter().zip(cells) {
+                let leaf = if quoted {
+                    Value::String(cell)
+                } else if cell.is_empty() {
+                    Value::Null
+                } else {
+                    serde_json::from_str(&cell).unwrap_or(Value::String(cell))
+                };
+                unflatten(&mut record, &name.split('.').collect::<Vec<_>>(), leaf);
+            }
+            let record: OwnedRecord<K, V> = serde_json::from_value(record)?;
+            batch.push((record.key, record.value));
+            count += 1;
+            if batch.len() == IMPORT_BATCH {
+                self.try_insert_batch(std::mem::take(&mut batch)).await?;
+            }
+        }
+        self.try_insert_batch(batch).await?;
+        Ok(count)
+    }
+    /// Streams `range` as one `{"key": ..., "value": ...}` JSON object per line.
+    pub async fn export_jsonl(
+        &self,
+        path: &str,
+        range: impl RangeBounds<K>,
+    ) -> anyhow::Result<usize>
+    where
+        K: Serialize,
+        V: Serialize,
+    {
+        let writer = BufWriter::new(File::create(path)?);
+        let (mut writer, rows) = self
+            .scan_detached(range, (writer, 0), |(writer, rows), key, value| {
+                serde_json::to_writer(
+                    &mut *writer,
+                    &Record {
+                        key: &key,
+                        value: &value,
+                    },
+                )?;
+                writeln!(writer)?;
+                *rows += 1;
+                Ok(())
+            })
+            .await?;
+        writer.flush()?;
+        Ok(rows)
+    }
+    pub async fn import_jsonl(&self, path: &str) -> anyhow::Result<usize>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        let text = std::fs::read_to_string(path)?;
+        let mut batch = Vec::with_capacity(IMPORT_BATCH);
+        let mut count = 0;
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            let record: OwnedRecord<K, V> = serde_json::from_str(line)?;
+            batch.push((record.key, record.value));
+            count += 1;
+            if batch.len() == IMPORT_BATCH {
+                self.try_insert_batch(std::mem::take(&mut batch)).await?;
+            }
+        }
+        self.try_insert_batch(batch).await?;
+        Ok(count)
+    }
+    /// Streams the values in `range` to a `.npy` array of `E` with shape `(rows, *shape)`.
+    pub async fn export_npy<E: NpyElement>(
+        &self,
+        path: &str,
+        range: impl RangeBounds<K>,
+        shape: &[usize],
+    ) -> anyhow::Result<usize>
+    where
+        V: Pod,
+    {
+        check_shape::<V, E>(shape)?;
+        let writer = NpyWriter::new(BufWriter::new(File::create(path)?), E::DESCR, shape)?;
+        let writer = self
+            .scan_detached(range, writer, |writer, _, value| {
+                writer.push(bytemuck::bytes_of(&value))
+            })
+            .await?;
+        let rows = writer.rows;
+        writer.finish()?;
+        Ok(rows)
+    }
+    /// Loads every row of a `.npy` array of `E` as one value, keyed by `key(row)`.
+    pub async fn import_npy<E: NpyElement>(
+        &self,
+        path: &str,
+        key: impl Fn(usize) -> K,
+    ) -> anyhow::Result<usize>
+    where
+        V: Pod,
+    {
+        let (shape, data) = read_npy::<E>(File::open(path)?)?;
+        self.import_rows::<E>(&shape, &data, key).await
+    }
+    /// Writes `range` to a `.npz` archive holding `keys.npy` and a `values.npy` array of `E`.
+    /// Both arrays are buffered in memory, since each archive member needs its row count in
+    /// its header, and written out once the scan ends.
+    pub async fn export_npz<E: NpyElement>(
+        &self,
+        path: &str,
+        range: impl RangeBounds<K>,
+        shape: &[usize],
+    ) -> anyhow::Result<usize>
+    where
+        K: NpyElement,
+        V: Pod,
+    {
+        check_shape::<V, E>(shape)?;
+        let arrays = (npy_buffer(K::DESCR, &[])?, npy_buffer(E::DESCR, shape)?);
+        let (keys, values) = self
+            .scan_detached(range, arrays, |(keys, values), key, value| {
+                keys.push(bytemuck::bytes_of(&key))?;
+                values.push(bytemuck::bytes_of(&value))
+            })
+            .await?;
+        let rows = keys.rows();
+        write_npz(
+            path,
+            vec![
+                ("keys.npy", keys.finish()?),
+                ("values.npy", values.finish()?),
+            ],
+        )?;
+        Ok(rows)
+    }
+    pub async fn import_npz<E: NpyElement>(&self, path: &str) -> anyhow::Result<usize>
+    where
+        K: NpyElement,
+        V: Pod,
+    {
+        let (_, keys) = read_npz::<K>(path, "keys.npy")?;
+        let (shape, values) = read_npz::<E>(path, "values.npy")?;
+        let keys = bytemuck::pod_collect_to_vec::<u8, K>(&keys);
+        anyhow::ensure!(
+            keys.len() == shape.first().copied().unwrap_or_default(),
+            "keys.npy and values.npy have different lengths."
+        );
+        self.import_rows::<E>(&shape, &values, |i| keys[i]).await
+    }
+    async fn import_rows<E: NpyElement>(
+        &self,
+        shape: &[usize],
+        data: &[u8],
+        key: impl Fn(usize) -> K,
+    ) -> anyhow::Result<usize>
+    where
+        V: Pod,
+    {
+        check_shape::<V, E>(shape.get(1..).unwrap_or_default())?;
+        let rows = data.chunks_exact(std::mem::size_of::<V>().max(1));
+        let mut count = 0;
+        for chunk in rows.collect::<Vec<_>>().chunks(IMPORT_BATCH) {
+            let batch = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, row)| (key(count + i), bytemuck::pod_read_unaligned::<V>(row)))
+                .collect::<Vec<_>>();
+            count += batch.len();
+            self.try_insert_batch(batch).await?;
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,123 @@
+use std::{marker::PhantomData, ops::Bound};
+
+use redb::{ReadableDatabase, ReadableTable, TableHandle};
+
+/// Maps the encoded bytes of a value to the encoded bytes of its index key.
+pub(crate) type Extractor = Box<dyn Fn(&[u8]) -> anyhow::Result<Vec<u8>> + Send>;
+
+/// Handle to a secondary index, returned by `Storage::add_index`.
+pub struct Index<I, IC> {
+    pub(crate) name: String,
+    types: PhantomData<(I, IC)>,
+}
+impl<I, IC> Index<I, IC> {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            types: PhantomData,
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Secondary indexes of one storage table, each kept in its own redb table of
+/// `hex(index key)/hex(primary key)` entries so that one index key can map to many records.
+pub(crate) struct Indexes {
+    inner: Vec<(String, Extractor)>,
+    /// Indexes stored in the database whose extractors have not been added since it opened.
+    missing: Vec<String>,
+}
+impl Indexes {
+    /// The indexes of `db`, none of which has an extractor yet.
+    pub(crate) fn open(db: &redb::Database) -> anyhow::Result<Self> {
+        let prefix = Self::table_name("");
+        let missing = db
+            .begin_read()?
+            .list_tables()?
+            .filter_map(|t| t.name().strip_prefix(&prefix).map(str::to_string))
+            .collect();
+        Ok(Self {
+            inner: Vec::new(),
+            missing,
+        })
+    }
+    pub(crate) fn table_name(name: &str) -> String {
+        format!("data.index.{name}")
+    }
+    fn entry_key(index_key: &[u8], primary: &[u8]) -> String {
+        format!("{}/{}", hex::encode(index_key), hex::encode(primary))
+    }
+    /// Replaces the index entry of `primary`, in the caller's write transaction.
+    pub(crate) fn update(
+        &self,
+        txn: &redb::WriteTransaction,
+        primary: &[u8],
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> anyhow::Result<()> {
+        if let Some(name) = self.missing.first() {
+            anyhow::bail!("Index {name} must be added again before writing to this storage.");
+        }
+        for (name, extract) in &self.inner {
+            let table_name = Self::table_name(name);
+            let mut table =
+                txn.open_table(redb::TableDefinition::<String, &[u8]>::new(&table_name))?;
+            if let Some(old) = old {
+                table.remove(Self::entry_key(&extract(old)?, primary))?;
+            }
+            if let Some(new) = new {
+                table.insert(Self::entry_key(&extract(new)?, primary), primary)?;
+            }
+        }
+        Ok(())
+    }
+    /// Adds an index, rebuilding its table from the current contents of `data`.
+    pub(crate) fn register(
+        &mut self,
+        txn: &redb::WriteTransaction,
+        data: redb::TableDefinition<'_, String, &[u8]>,
+        name: &str,
+        extract: Extractor,
+    ) -> anyhow::Result<()> {
+        let table_name = Self::table_name(name);
+        let definition = redb::TableDefinition::<String, &[u8]>::new(&table_name);
+        txn.delete_table(definition)?;
+        {
+            let source = txn.open_table(data)?;
+            let mut table = txn.open_table(definition)?;
+            for entry in source.iter()? {
+                let (k, v) = entry?;
+                let primary = hex::decode(k.value())?;
+                table.insert(
+                    Self::entry_key(&extract(v.value())?, &primary),
+                    primary.as_slice(),
+                )?;
+            }
+        }
+        self.missing.retain(|n| n != name);
+        self.inner.retain(|(n, _)| n != name);
+        self.inner.push((name.to_string(), extract));
+        Ok(())
+    }
+    /// Bounds over the entry keys of every index key inside the given encoded bounds.
+    pub(crate) fn entry_bounds(
+        start: Bound<Vec<u8>>,
+        end: Bound<Vec<u8>>,
+    ) -> (Bound<String>, Bound<String>) {
+        // '/' sorts just below the hex digits, so "{key}/" precedes every entry of `key` and
+        // "{key}0" follows them all while staying below any longer index key.
+        let start = match start {
+            Bound::Included(k) => Bound::Included(format!("{}/", hex::encode(k))),
+            Bound::Excluded(k) => Bound::Included(format!("{}0", hex::encode(k))),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match end {
+            Bound::Included(k) => Bound::Excluded(format!("{}0", hex::encode(k))),
+            Bound::Excluded(k) => Bound::Excluded(format!("{}/", hex::encode(k))),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        (start, end)
+    }
+}
@@ -0,0 +1,75 @@
+use redb::{ReadableDatabase, ReadableTable, ReadableTableMetadata, TableHandle};
+
+/// Size and contents of a storage database.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StorageStats {
+    /// On-disk size, or `None` for in-memory stores.
+    pub file_size: Option<u64>,
+    pub allocated_bytes: u64,
+    pub fragmented_bytes: u64,
+    pub tables: Vec<TableStats>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TableStats {
+    pub name: String,
+    pub entries: u64,
+    pub stored_bytes: u64,
+    pub metadata_bytes: u64,
+    pub fragmented_bytes: u64,
+}
+
+/// Copies every table visible to `txn` into `target` in one write transaction, replacing the
+/// tables `target` held before so that an earlier copy never leaks into the new one.
+pub(crate) fn copy_tables(
+    txn: &redb::ReadTransaction,
+    target: &redb::Database,
+) -> anyhow::Result<()> {
+    let write = target.begin_write()?;
+    for handle in write.list_tables()?.collect::<Vec<_>>() {
+        write.delete_table(handle)?;
+    }
+    for handle in txn.list_tables()? {
+        let definition: redb::TableDefinition<'_, String, &[u8]> =
+            redb::TableDefinition::new(handle.name());
+        let source = txn.open_table(definition)?;
+        let mut destination = write.open_table(definition)?;
+        for entry in source.iter()? {
+            let (k, v) = entry?;
+            destination.insert(k.value(), v.value())?;
+        }
+    }
+    write.commit()?;
+    Ok(())
+}
+
+pub(crate) fn stats(db: &redb::Database, path: Option<&str>) -> anyhow::Result<StorageStats> {
+    let file_size = path.map(std::fs::metadata).transpose()?.map(|m| m.len());
+    let database = {
+        let txn = db.begin_write()?;
+        let stats = txn.stats()?;
+        txn.abort()?;
+        stats
+    };
+    let txn = db.begin_read()?;
+    let mut tables = Vec::new();
+    for handle in txn.list_tables()? {
+        let definition: redb::TableDefinition<'_, String, &[u8]> =
+            redb::TableDefinition::new(handle.name());
+        let table = txn.open_table(definition)?;
+        let stats = table.stats()?;
+        tables.push(TableStats {
+            name: handle.name().to_string(),
+            entries: table.len()?,
+            stored_bytes: stats.stored_bytes(),
+            metadata_bytes: stats.metadata_bytes(),
+            fragmented_bytes: stats.fragmented_bytes(),
+        });
+    }
+    Ok(StorageStats {
+        file_size,
+        allocated_bytes: database.allocated_pages() * database.page_size() as u64,
+        fragmented_bytes: database.fragmented_bytes(),
+        tables,
+    })
+}
@@ -1,65 +1,374 @@
-use bytemuck::{Pod, bytes_of};
+use bytemuck::Pod;
 use redb::ReadableDatabase;
-use std::sync::{mpsc::Sender, oneshot};
+use std::{
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+    sync::{mpsc::Sender, oneshot},
+};
 
-pub enum StorageCommand<K: Pod, V: Pod> {
+use crate::cpu::storage::{
+    codec::{Codec, PodCodec, PodSliceCodec},
+    index::{Extractor, Index, Indexes},
+    maintenance::{StorageStats, copy_tables},
+    snapshot::{DataTable, ReadSnapshot},
+    watch::{Overflow, RawEvent, Subscribers, Watch, WatchFilter},
+};
+
+pub mod codec;
+pub mod export;
+pub mod index;
+pub mod maintenance;
+pub mod recorder;
+pub mod schema;
+pub mod snapshot;
+pub mod watch;
+
+pub type ViewFn = Box<dyn FnOnce(Option<&[u8]>) + Send>;
+pub type ScanFn =
+    Box<dyn FnOnce(&mut dyn Iterator<Item = anyhow::Result<(Vec<u8>, Vec<u8>)>>) + Send>;
+
+/// Where a write reports its outcome. Without one, a failed write panics the worker.
+pub type WriteResponse = Option<tokio::sync::oneshot::Sender<anyhow::Result<()>>>;
+
+pub enum StorageCommand<K, V> {
     Insert {
         key: K,
         value: V,
+        response: WriteResponse,
+    },
+    InsertBatch {
+        entries: Vec<(K, V)>,
+        response: WriteResponse,
     },
     Remove {
         key: K,
+        response: WriteResponse,
+    },
+    RemoveRange {
+        start: Bound<K>,
+        end: Bound<K>,
+        response: WriteResponse,
     },
     Get {
         key: K,
-        response: oneshot::Sender<Option<V>>,
+        response: oneshot::Sender<anyhow::Result<Option<V>>>,
+    },
+    View {
+        key: K,
+        view: ViewFn,
+    },
+    Range {
+        start: Bound<K>,
+        end: Bound<K>,
+        response: oneshot::Sender<anyhow::Result<Vec<(K, V)>>>,
+    },
+    Scan {
+        start: Bound<K>,
+        end: Bound<K>,
+        scan: ScanFn,
+    },
+    AddIndex {
+        name: String,
+        extractor: Extractor,
+        response: oneshot::Sender<anyhow::Result<()>>,
+    },
+    IndexRange {
+        index: String,
+        start: Bound<Vec<u8>>,
+        end: Bound<Vec<u8>>,
+        response: oneshot::Sender<anyhow::Result<Vec<(K, V)>>>,
+    },
+    Watch {
+        filter: WatchFilter,
+        sender: tokio::sync::broadcast::Sender<RawEvent>,
+    },
+    ReadSnapshot {
+        response: oneshot::Sender<anyhow::Result<Option<DataTable>>>,
+    },
+    Snapshot {
+        path: String,
+        response: oneshot::Sender<anyhow::Result<()>>,
+    },
+    Backup {
+        path: String,
+        response: tokio::sync::oneshot::Sender<anyhow::Result<()>>,
+    },
+    Compact {
+        response: oneshot::Sender<anyhow::Result<bool>>,
+    },
+    Stats {
+        response: oneshot::Sender<anyhow::Result<StorageStats>>,
     },
     Close,
 }
 
-pub struct Storage<K: Pod, V: Pod> {
+pub struct Storage<K, V, KC = PodCodec, VC = PodCodec> {
     sender: Sender<StorageCommand<K, V>>,
     thread_handle: std::thread::JoinHandle<()>,
+    codecs: PhantomData<(KC, VC)>,
 }
-impl<K: Pod + Send, V: Pod + Send> Storage<K, V> {
+impl<K, V, KC, VC> Storage<K, V, KC, VC>
+where
+    K: Send + 'static,
+    V: Send + 'static,
+    KC: Codec<K>,
+    VC: Codec<V>,
+{
+    /// Opens or creates the database at `path`. Panics if it holds a schema recorded for other
+    /// key or value types; `open` also checks the version.
     pub fn new(path: &str) -> Self {
+        let db = redb::Database::create(path).expect("Storage database creation failed.");
+        schema::check_types::<K, V>(&db, path).expect("Storage schema does not fit.");
+        Self::spawn(db, Some(path.to_string()))
+    }
+    /// Keeps the whole database in memory, for tests and runs that need no file.
+    pub fn in_memory() -> Self {
+        Self::spawn(
+            redb::Database::builder()
+                .create_with_backend(redb::backends::InMemoryBackend::new())
+                .expect("In-memory storage database creation failed."),
+            None,
+        )
+    }
+    /// Opens an in-memory store holding a copy of the database file at `path`.
+    pub fn load_into_memory(path: &str) -> anyhow::Result<Self> {
+        let file = redb::Database::open(path)?;
+        schema::check_types::<K, V>(&file, path)?;
+        let memory =
+            redb::Database::builder().create_with_backend(redb::backends::InMemoryBackend::new())?;
+        copy_tables(&file.begin_read()?, &memory)?;
+        Ok(Self::spawn(memory, None))
+    }
+    fn spawn(mut db: redb::Database, path: Option<String>) -> Self {
         let (tx, rx) = std::sync::mpsc::channel();
-        let path = path.to_string();
+        let mut indexes = Indexes::open(&db).expect("Could not read storage indexes.");
         let thread_handle = std::thread::spawn(move || {
-            let db = redb::Database::create(&path).expect("Storage database creation failed.");
             let table: redb::TableDefinition<'static, String, &[u8]> =
                 redb::TableDefinition::new("data");
+            let mut subscribers = Subscribers::default();
             while let Ok(cmd) = rx.recv() {
                 match cmd {
-                    StorageCommand::Insert { key, value } => {
-                        let txn = db.begin_write().unwrap();
-                        {
-                            let mut t = txn.open_table(table).unwrap();
-                            let key_string = hex::encode(bytes_of(&key));
-                            t.insert(&key_string, &bytes_of::<V>(&value)).unwrap();
-                        }
-                        txn.commit().unwrap();
+                    StorageCommand::Insert {
+                        key,
+                        value,
+                        response,
+                    } => {
+                        let (key, value) = (KC::encode(&key), VC::encode(&value));
+                        let result = write(&db, |txn| {
+                            let mut t = txn.open_table(table)?;
+                            let old = t.insert(&hex::encode(&key), value.as_slice())?;
+                            let old = old.map(|b| b.value().to_vec());
+                            indexes.update(txn, &key, old.as_deref(), Some(&value))?;
+                            Ok(vec![RawEvent {
+                                key,
+                                value: Some(value),
+                            }])
+                        });
+                        respond(&mut subscribers, response, result);
+                    }
+                    StorageCommand::InsertBatch { entries, response } => {
+                        let result = write(&db, |txn| {
+                            let mut events = Vec::with_capacity(entries.len());
+                            let mut t = txn.open_table(table)?;
+                            for (key, value) in entries {
+                                let (key, value) = (KC::encode(&key), VC::encode(&value));
+                                let old = t.insert(&hex::encode(&key), value.as_slice())?;
+                                let old = old.map(|b| b.value().to_vec());
+                                indexes.update(txn, &key, old.as_deref(), Some(&value))?;
+                                events.push(RawEvent {
+                                    key,
+                                    value: Some(value),
+                                });
+                            }
+                            Ok(events)
+                        });
+                        respond(&mut subscribers, response, result);
                     }
                     StorageCommand::Get { key, response } => {
                         let txn = db.begin_read().unwrap();
-                        let t = txn.open_table(table).unwrap();
-                        let key_string = hex::encode(bytes_of(&key));
-                        let result = t.get(&key_string).unwrap().map(|b| {
-                            let bytes = b.value();
-                            let mut aligned = vec![0u8; std::mem::size_of::<V>()];
-                            aligned.copy_from_slice(bytes);
-                            bytemuck::from_bytes::<V>(&aligned).clone()
+                        let result = match txn.open_table(table) {
+                            Ok(t) => {
+                                let key_string = hex::encode(KC::encode(&key));
+                                t.get(&key_string)
+                                    .unwrap()
+                                    .map(|b| VC::decode(b.value()))
+                                    .transpose()
+                            }
+                            Err(redb::TableError::TableDoesNotExist(_)) => Ok(None),
+                            Err(e) => Err(e.into()),
+                        };
+                        let _ = response.send(result);
+                    }
+                    StorageCommand::View { key, view } => {
+                        let txn = db.begin_read().unwrap();
+                        match txn.open_table(table) {
+                            Ok(t) => {
+                                let key_string = hex::encode(KC::encode(&key));
+                                let guard = t.get(&key_string).unwrap();
+                                view(guard.as_ref().map(|b| b.value()));
+                            }
+                            Err(_) => view(None),
+                        }
+                    }
+                    StorageCommand::Range {
+                        start,
+                        end,
+                        response,
+                    } => {
+                        let txn = db.begin_read().unwrap();
+                        let result = match txn.open_table(table) {
+                            Ok(t) => {
+                                let start = start.map(|k| hex::encode(KC::encode(&k)));
+                                let end = end.map(|k| hex::encode(KC::encode(&k)));
+                                t.range::<String>((start, end))
+                                    .unwrap()
+                                    .map(|entry| {
+                                        let (k, v) = entry?;
+                                        Ok((
+                                            KC::decode(&hex::decode(k.value())?)?,
+                                            VC::decode(v.value())?,
+                                        ))
+                                    })
+                                    .collect()
+                            }
+                            Err(redb::TableError::TableDoesNotExist(_)) => Ok(Vec::new()),
+                            Err(e) => Err(e.into()),
+                        };
+                        let _ = response.send(result);
+                    }
+                    StorageCommand::Scan { start, end, scan } => {
+                        let txn = db.begin_read().unwrap();
+                        match txn.open_table(table) {
+                            Ok(t) => {
+                                let start = start.map(|k| hex::encode(KC::encode(&k)));
+                                let end = end.map(|k| hex::encode(KC::encode(&k)));
+                                let mut entries =
+                                    t.range::<String>((start, end)).unwrap().map(|entry| {
+                                        let (k, v) = entry?;
+                                        Ok((hex::decode(k.value())?, v.value().to_vec()))
+                                    });
+                                scan(&mut entries);
+                            }
+                            Err(_) => scan(&mut std::iter::empty()),
+                        }
+                    }
+                    StorageCommand::Remove { key, response } => {
+                        let key = KC::encode(&key);
+                        let result = write(&db, |txn| {
+                            let mut t = txn.open_table(table)?;
+                            let old = t.remove(&hex::encode(&key))?;
+                            let old = old.map(|b| b.value().to_vec());
+                            indexes.update(txn, &key, old.as_deref(), None)?;
+                            Ok(match old {
+                                Some(_) => vec![RawEvent { key, value: None }],
+                                None => Vec::new(),
+                            })
+                        });
+                        respond(&mut subscribers, response, result);
+                    }
+                    StorageCommand::RemoveRange {
+                        start,
+                        end,
+                        response,
+                    } => {
+                        let result = write(&db, |txn| {
+                            let mut t = txn.open_table(table)?;
+                            let start = start.map(|k| hex::encode(KC::encode(&k)));
+                            let end = end.map(|k| hex::encode(KC::encode(&k)));
+                            let removed = t
+                                .extract_from_if::<String, _>((start, end), |_, _| true)?
+                                .map(|entry| {
+                                    let (k, v) = entry?;
+                                    Ok((hex::decode(k.value())?, v.value().to_vec()))
+                                })
+                                .collect::<anyhow::Result<Vec<_>>>()?;
+                            for (key, old) in &removed {
+                                indexes.update(txn, key, Some(old), None)?;
+                            }
+                            Ok(removed
+                                .into_iter()
+                                .map(|(key, _)| RawEvent { key, value: None })
+                                .collect())
                         });
+                        respond(&mut subscribers, response, result);
+                    }
+                    StorageCommand::AddIndex {
+                        name,
+                        extractor,
+                        response,
+                    } => {
+                        let result =
+                            db.begin_write()
+                                .map_err(anyhow::Error::from)
+                                .and_then(|txn| {
+                                    indexes.register(&txn, table, &name, extractor)?;
+                                    Ok(txn.commit()?)
+                                });
+                        let _ = response.send(result);
+                    }
+                    StorageCommand::IndexRange {
+                        index,
+                        start,
+                        end,
+                        response,
+                    } => {
+                        let result = (|| {
+                            let txn = db.begin_read()?;
+                            let name = Indexes::table_name(&index);
+                            let entries =
+                                txn.open_table(redb::TableDefinition::<String, &[u8]>::new(&name))?;
+                            let t = txn.open_table(table)?;
+                            let mut found = Vec::new();
+                            for entry in entries.range(Indexes::entry_bounds(start, end))? {
+                                let primary = entry?.1.value().to_vec();
+                                let value = t.get(hex::encode(&primary))?.ok_or_else(|| {
+                                    anyhow::anyhow!("Index {index} is out of date.")
+                                })?;
+                                found.push((KC::decode(&primary)?, VC::decode(value.value())?));
+                            }
+                            Ok(found)
+                        })();
+                        let _ = response.send(result);
+                    }
+                    StorageCommand::Watch { filter, sender } => subscribers.add(filter, sender),
+                    StorageCommand::ReadSnapshot { response } => {
+                        let result = db
+                            .begin_read()
+                            .map_err(anyhow::Error::from)
+                            .and_then(|txn| match txn.open_table(table) {
+                                Ok(t) => Ok(Some(t)),
+                                Err(redb::TableError::TableDoesNotExist(_)) => Ok(None),
+                                Err(e) => Err(e.into()),
+                            });
+                        let _ = response.send(result);
+                    }
+                    StorageCommand::Snapshot { path, response } => {
+                        let result = redb::Database::create(&path)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|target| copy_tables(&db.begin_read()?, &target));
                         let _ = response.send(result);
                     }
-                    StorageCommand::Remove { key } => {
-                        let txn = db.begin_write().unwrap();
-                        {
-                            let mut t = txn.open_table(table).unwrap();
-                            let key_string = hex::encode(bytes_of(&key));
-                            t.remove(&key_string).unwrap();
+                    StorageCommand::Backup { path, response } => match db.begin_read() {
+                        // The copy reads from this MVCC snapshot on its own thread, so the
+                        // worker keeps committing writes while it runs.
+                        Ok(txn) => {
+                            std::thread::spawn(move || {
+                                let result = redb::Database::create(&path)
+                                    .map_err(anyhow::Error::from)
+                                    .and_then(|target| copy_tables(&txn, &target));
+                                drop(txn);
+                                let _ = response.send(result);
+                            });
                         }
-                        txn.commit().unwrap();
+                        Err(e) => {
+                            let _ = response.send(Err(e.into()));
+                        }
+                    },
+                    StorageCommand::Compact { response } => {
+                        let _ = response.send(db.compact().map_err(anyhow::Error::from));
+                    }
+                    StorageCommand::Stats { response } => {
+                        let _ = response.send(maintenance::stats(&db, path.as_deref()));
                     }
                     StorageCommand::Close => break,
                 }
@@ -68,14 +377,54 @@ impl<K: Pod + Send, V: Pod + Send> Storage<K, V> {
         Self {
             sender: tx,
             thread_handle,
+            codecs: PhantomData,
         }
     }
-    pub async fn insert(&self, key: K, value: V) {
+    pub(crate) fn send(&self, command: StorageCommand<K, V>) {
         self.sender
-            .send(StorageCommand::Insert { key, value })
-            .unwrap();
+            .send(command)
+            .expect("Could not send storage command.");
+    }
+    /// Queues the write without waiting for it to commit. A queued write that fails, such as
+    /// one to a reopened store whose indexes have not been added again, stops the worker.
+    pub async fn insert(&self, key: K, value: V) {
+        self.send(StorageCommand::Insert {
+            key,
+            value,
+            response: None,
+        });
+    }
+    /// Like `insert`, but waits for the commit and returns failed writes.
+    pub async fn try_insert(&self, key: K, value: V) -> anyhow::Result<()> {
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        self.send(StorageCommand::Insert {
+            key,
+            value,
+            response: Some(resp_tx),
+        });
+        resp_rx.await?
+    }
+    /// Inserts every entry in a single write transaction.
+    pub async fn insert_batch(&self, entries: Vec<(K, V)>) {
+        self.send(StorageCommand::InsertBatch {
+            entries,
+            response: None,
+        });
+    }
+    pub async fn try_insert_batch(&self, entries: Vec<(K, V)>) -> anyhow::Result<()> {
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        self.send(StorageCommand::InsertBatch {
+            entries,
+            response: Some(resp_tx),
+        });
+        resp_rx.await?
     }
     pub async fn get(&self, key: K) -> Option<V> {
+        self.try_get(key)
+            .await
+            .expect("Could not decode stored value.")
+    }
+    pub async fn try_get(&self, key: K) -> anyhow::Result<Option<V>> {
         let (resp_tx, resp_rx) = oneshot::channel();
         self.sender
             .send(StorageCommand::Get {
@@ -85,8 +434,214 @@ impl<K: Pod + Send, V: Pod + Send> Storage<K, V> {
             .expect("Unable to send get message.");
         resp_rx.recv().unwrap()
     }
+    /// Runs `f` on the worker thread against the raw stored bytes, without an intermediate copy.
+    pub async fn view<R: Send + 'static>(
+        &self,
+        key: K,
+        f: impl FnOnce(Option<&[u8]>) -> R + Send + 'static,
+    ) -> R {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.sender
+            .send(StorageCommand::View {
+                key,
+                view: Box::new(move |bytes| {
+                    let _ = resp_tx.send(f(bytes));
+                }),
+            })
+            .expect("Unable to send view message.");
+        resp_rx.recv().unwrap()
+    }
+    /// Returns the entries whose encoded keys fall inside `range`, in encoded byte order.
+    pub async fn range(&self, range: impl RangeBounds<K>) -> anyhow::Result<Vec<(K, V)>>
+    where
+        K: Clone,
+    {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(StorageCommand::Range {
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            response: resp_tx,
+        });
+        resp_rx.recv().unwrap()
+    }
+    /// Feeds the entries inside `range` to `visit` one at a time on the worker thread, without
+    /// collecting them first, and hands back the final `state`.
+    pub async fn scan<S: Send + 'static>(
+        &self,
+        range: impl RangeBounds<K>,
+        mut state: S,
+        mut visit: impl FnMut(&mut S, K, V) -> anyhow::Result<()> + Send + 'static,
+    ) -> anyhow::Result<S>
+    where
+        K: Clone,
+    {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(StorageCommand::Scan {
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            scan: Box::new(move |entries| {
+                let result = (|| {
+                    for entry in entries {
+                        let (k, v) = entry?;
+                        visit(&mut state, KC::decode(&k)?, VC::decode(&v)?)?;
+                    }
+                    Ok(state)
+                })();
+                let _ = resp_tx.send(result);
+            }),
+        });
+        resp_rx.recv().unwrap()
+    }
     pub async fn remove(&self, key: K) {
-        self.sender.send(StorageCommand::Remove { key }).unwrap();
+        self.send(StorageCommand::Remove {
+            key,
+            response: None,
+        });
+    }
+    pub async fn try_remove(&self, key: K) -> anyhow::Result<()> {
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        self.send(StorageCommand::Remove {
+            key,
+            response: Some(resp_tx),
+        });
+        resp_rx.await?
+    }
+    pub async fn remove_range(&self, range: impl RangeBounds<K>)
+    where
+        K: Clone,
+    {
+        self.send(StorageCommand::RemoveRange {
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            response: None,
+        });
+    }
+    pub async fn try_remove_range(&self, range: impl RangeBounds<K>) -> anyhow::Result<()>
+    where
+        K: Clone,
+    {
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        self.send(StorageCommand::RemoveRange {
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            response: Some(resp_tx),
+        });
+        resp_rx.await?
+    }
+    /// Declares a secondary index on the key `extract` pulls out of each value. The index is
+    /// rebuilt from the current contents, then kept up to date by every write. Extractors only
+    /// live in memory, so after reopening a file its indexes must be added again before any
+    /// write, which fails until they are.
+    pub async fn add_index<I, IC: Codec<I>>(
+        &self,
+        name: &str,
+        extract: impl Fn(&V) -> I + Send + 'static,
+    ) -> anyhow::Result<Index<I, IC>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(StorageCommand::AddIndex {
+            name: name.to_string(),
+            extractor: Box::new(move |bytes| Ok(IC::encode(&extract(&VC::decode(bytes)?)))),
+            response: resp_tx,
+        });
+        resp_rx.recv().unwrap()?;
+        Ok(Index::new(name))
+    }
+    /// Every record whose index key equals `key`, in primary key order.
+    pub async fn get_by_index<I, IC: Codec<I>>(
+        &self,
+        index: &Index<I, IC>,
+        key: I,
+    ) -> anyhow::Result<Vec<(K, V)>> {
+        let key = IC::encode(&key);
+        self.index_range(index, Bound::Included(key.clone()), Bound::Included(key))
+            .await
+    }
+    /// Every record whose index key falls inside `range`, ordered by encoded index key.
+    pub async fn range_by_index<I, IC: Codec<I>>(
+        &self,
+        index: &Index<I, IC>,
+        range: impl RangeBounds<I>,
+    ) -> anyhow::Result<Vec<(K, V)>> {
+        let start = range.start_bound().map(IC::encode);
+        let end = range.end_bound().map(IC::encode);
+        self.index_range(index, start, end).await
+    }
+    async fn index_range<I, IC>(
+        &self,
+        index: &Index<I, IC>,
+        start: Bound<Vec<u8>>,
+        end: Bound<Vec<u8>>,
+    ) -> anyhow::Result<Vec<(K, V)>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(StorageCommand::IndexRange {
+            index: index.name.clone(),
+            start,
+            end,
+            response: resp_tx,
+        });
+        resp_rx.recv().unwrap()
+    }
+    /// Streams committed inserts and removes of `key`, buffering up to `capacity` events. A
+    /// capacity of 0 is raised to 1.
+    pub async fn watch(&self, key: K, capacity: usize, overflow: Overflow) -> Watch<K, V, KC, VC> {
+        self.subscribe(WatchFilter::Key(KC::encode(&key)), capacity, overflow)
+    }
+    /// Like `watch`, for every key whose encoded bytes start with `prefix`.
+    pub async fn watch_prefix(
+        &self,
+        prefix: impl Into<Vec<u8>>,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Watch<K, V, KC, VC> {
+        self.subscribe(WatchFilter::Prefix(prefix.into()), capacity, overflow)
+    }
+    fn subscribe(
+        &self,
+        filter: WatchFilter,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Watch<K, V, KC, VC> {
+        let (sender, receiver) = tokio::sync::broadcast::channel(capacity.max(1));
+        self.send(StorageCommand::Watch { filter, sender });
+        Watch::new(receiver, overflow)
+    }
+    /// Takes a consistent view of every write sent so far, readable from any number of threads
+    /// without going through the worker. `compact` fails while a snapshot is alive.
+    pub async fn read_snapshot(&self) -> anyhow::Result<ReadSnapshot<K, V, KC, VC>> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(StorageCommand::ReadSnapshot { response: resp_tx });
+        Ok(ReadSnapshot::new(resp_rx.recv().unwrap()?))
+    }
+    /// Writes the current contents to a database file at `path`, e.g. to keep an in-memory run.
+    /// Whatever a database already at `path` held is replaced.
+    pub async fn snapshot(&self, path: &str) -> anyhow::Result<()> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(StorageCommand::Snapshot {
+            path: path.to_string(),
+            response: resp_tx,
+        });
+        resp_rx.recv().unwrap()
+    }
+    /// Copies a consistent view of the database to `path` without holding up later writes,
+    /// replacing whatever a database already at `path` held.
+    pub async fn backup(&self, path: &str) -> anyhow::Result<()> {
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        self.send(StorageCommand::Backup {
+            path: path.to_string(),
+            response: resp_tx,
+        });
+        resp_rx.await?
+    }
+    /// Reclaims free space in the database file. Returns whether anything was compacted.
+    pub async fn compact(&self) -> anyhow::Result<bool> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(StorageCommand::Compact { response: resp_tx });
+        resp_rx.recv().unwrap()
+    }
+    pub async fn stats(&self) -> anyhow::Result<StorageStats> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.send(StorageCommand::Stats { response: resp_tx });
+        resp_rx.recv().unwrap()
     }
     pub async fn close(self) {
         self.sender
@@ -97,3 +652,55 @@ impl<K: Pod + Send, V: Pod + Send> Storage<K, V> {
             .expect("Could not join storage thread.");
     }
 }
+/// Runs `f` in a write transaction, committing it if `f` succeeds and aborting it otherwise.
+fn write(
+    db: &redb::Database,
+    f: impl FnOnce(&redb::WriteTransaction) -> anyhow::Result<Vec<RawEvent>>,
+) -> anyhow::Result<Vec<RawEvent>> {
+    let txn = db.begin_write()?;
+    match f(&txn) {
+        Ok(events) => {
+            txn.commit()?;
+            Ok(events)
+        }
+        Err(e) => {
+            txn.abort()?;
+            Err(e)
+        }
+    }
+}
+
+/// Publishes the events of a committed write and reports how it went.
+fn respond(
+    subscribers: &mut Subscribers,
+    response: WriteResponse,
+    result: anyhow::Result<Vec<RawEvent>>,
+) {
+    let result = result.map(|events| subscribers.publish(events));
+    match response {
+        Some(response) => {
+            let _ = response.send(result);
+        }
+        None => result.expect("Storage write failed."),
+    }
+}
+
+impl<K, T, KC> Storage<K, Vec<T>, KC, PodSliceCodec>
+where
+    K: Send + 'static,
+    T: Pod + Send,
+    KC: Codec<K>,
+{
+    /// Hands `f` the stored slice, borrowed straight from the database page when it is aligned.
+    pub async fn view_slice<R: Send + 'static>(
+        &self,
+        key: K,
+        f: impl FnOnce(Option<&[T]>) -> R + Send + 'static,
+    ) -> anyhow::Result<R> {
+        self.view(key, move |bytes| match bytes {
+            Some(bytes) => Ok(f(Some(&PodSliceCodec::view::<T>(bytes)?))),
+            None => Ok(f(None)),
+        })
+        .await
+    }
+}
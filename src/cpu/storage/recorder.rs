@@ -0,0 +1,325 @@
+use std::ops::{Bound, RangeBounds};
+
+use bytemuck::Pod;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    cpu::{
+        simulation::tecs::World,
+        storage::{
+            Storage, StorageCommand,
+            codec::{Codec, PodCodec, PodSliceCodec},
+            export::{NpyElement, check_shape, npy_buffer, read_npz, write_npz},
+        },
+    },
+    gpu::buffer::Buffer,
+};
+
+/// Identifies one recorded value: an entity or user-chosen channel at a given tick.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub struct FrameKey {
+    pub tick: u64,
+    pub channel: u64,
+}
+
+/// Big-endian so that stored keys sort by tick, then by channel.
+pub struct FrameKeyCodec;
+impl Codec<FrameKey> for FrameKeyCodec {
+    fn encode(value: &FrameKey) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16);
+        bytes.extend_from_slice(&value.tick.to_be_bytes());
+        bytes.extend_from_slice(&value.channel.to_be_bytes());
+        bytes
+    }
+    fn decode(bytes: &[u8]) -> anyhow::Result<FrameKey> {
+        anyhow::ensure!(
+            bytes.len() == 16,
+            "Frame keys are 16 bytes, found {}.",
+            bytes.len()
+        );
+        Ok(FrameKey {
+            tick: u64::from_be_bytes(bytes[..8].try_into()?),
+            channel: u64::from_be_bytes(bytes[8..].try_into()?),
+        })
+    }
+}
+
+/// Bounds over the frame keys of every tick inside `ticks`.
+fn frame_bounds(ticks: impl RangeBounds<u64>) -> (Bound<FrameKey>, Bound<FrameKey>) {
+    let start = match ticks.start_bound() {
+        Bound::Included(&t) => Bound::Included(FrameKey {
+            tick: t,
+            channel: 0,
+        }),
+        Bound::Excluded(&t) => Bound::Excluded(FrameKey {
+            tick: t,
+            channel: u64::MAX,
+        }),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    let end = match ticks.end_bound() {
+        Bound::Included(&t) => Bound::Included(FrameKey {
+            tick: t,
+            channel: u64::MAX,
+        }),
+        Bound::Excluded(&t) => Bound::Excluded(FrameKey {
+            tick: t,
+            channel: 0,
+        }),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    (start, end)
+}
+
+/// Every value recorded on one tick, ordered by channel.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Frame<V> {
+    pub tick: u64,
+    pub values: Vec<(u64, V)>,
+}
+
+/// Persists per-tick simulation output and reads it back by tick, channel or tick range.
+pub struct Recorder<V, VC = PodCodec> {
+    storage: Storage<FrameKey, V, FrameKeyCodec, VC>,
+    every: u64,
+    retain: Option<u64>,
+}
+impl<V: Send + 'static, VC: Codec<V>> Recorder<V, VC> {
+    /// Records one tick in `every` and, with `retain`, drops frames older than that many ticks.
+    pub fn new(path: &str, every: u64, retain: Option<u64>) -> Self {
+        assert!(every > 0, "Recorder must record at least every tick.");
+        Self {
+            storage: Storage::new(path),
+            every,
+            retain,
+        }
+    }
+    pub fn in_memory(every: u64, retain: Option<u64>) -> Self {
+        assert!(every > 0, "Recorder must record at least every tick.");
+        Self {
+            storage: Storage::in_memory(),
+            every,
+            retain,
+        }
+    }
+    pub fn should_record(&self, tick: u64) -> bool {
+        tick.is_multiple_of(self.every)
+    }
+    /// Writes a frame in one transaction. Ticks skipped by downsampling are ignored.
+    pub fn record(&self, tick: u64, values: impl IntoIterator<Item = (u64, V)>) {
+        if !self.should_record(tick) {
+            return;
+        }
+        let entries = values
+            .into_iter()
+            .map(|(channel, value)| (FrameKey { tick, channel }, value))
+            .collect();
+        self.storage.send(StorageCommand::InsertBatch {
+            entries,
+            response: None,
+        });
+        if let Some(retain) = self.retain
+            && tick >= retain
+        {
+            self.storage.send(StorageCommand::RemoveRange {
+                start: Bound::Unbounded,
+                end: Bound::Included(FrameKey {
+                    tick: tick - retain,
+                    channel: u64::MAX,
+                }),
+                response: None,
+            });
+        }
+    }
+    /// Records component `V` of every entity that has one, keyed by the entity's bits.
+    pub fn record_components(&self, tick: u64, world: &World)
+    where
+        V: hecs::Component + Clone,
+    {
+        if !self.should_record(tick) {
+            return;
+        }
+        let mut query = world.query::<(hecs::Entity, &V)>();
+        let values = query
+            .iter()
+            .map(|(entity, value)| (entity.to_bits().get(), value.clone()))
+            .collect::<Vec<_>>();
+        self.record(tick, values);
+    }
+    pub async fn tick(&self, tick: u64) -> anyhow::Result<Vec<(u64, V)>> {
+        let values = self
+            .storage
+            .range(
+                FrameKey { tick, channel: 0 }..=FrameKey {
+                    tick,
+                    channel: u64::MAX,
+                },
+            )
+            .await?;
+        Ok(values.into_iter().map(|(k, v)| (k.channel, v)).collect())
+    }
+    /// Values of one channel over `ticks`, as `(tick, value)` pairs. Seeks straight to the
+    /// channel's key on each recorded tick, so other channels are never read.
+    pub async fn channel(
+        &self,
+        channel: u64,
+        ticks: impl RangeBounds<u64>,
+    ) -> anyhow::Result<Vec<(u64, V)>> {
+        let snapshot = self.storage.read_snapshot().await?;
+        let (_, end) = frame_bounds((Bound::Unbounded, ticks.end_bound().cloned()));
+        let mut next = match ticks.start_bound() {
+            Bound::Included(&tick) => Some(tick),
+            Bound::Excluded(&tick) => tick.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let mut values = Vec::new();
+        while let Some(tick) = next {
+            let from = FrameKey { tick, channel };
+            let Some(key) = snapshot.first_key((Bound::Included(from), end))? else {
+                break;
+            };
+            if key.channel < channel {
+                next = Some(key.tick);
+                continue;
+            }
+            if key.channel == channel
+                && let Some(value) = snapshot.get(&key)?
+            {
+                values.push((key.tick, value));
+            }
+            next = key.tick.checked_add(1);
+        }
+        Ok(values)
+    }
+    pub async fn frames(
+        &self,
+        ticks: impl RangeBounds<u64>,
+    ) -> anyhow::Result<impl Iterator<Item = Frame<V>>> {
+        let mut frames: Vec<Frame<V>> = Vec::new();
+        for (key, value) in self.storage.range(frame_bounds(ticks)).await? {
+            match frames.last_mut() {
+                Some(frame) if frame.tick == key.tick => frame.values.push((key.channel, value)),
+                _ => frames.push(Frame {
+                    tick: key.tick,
+                    values: vec![(key.channel, value)],
+                }),
+            }
+        }
+        Ok(frames.into_iter())
+    }
+    /// Streams the frames in `ticks` to CSV, with `key.tick` and `key.channel` columns.
+    pub async fn export_csv(
+        &self,
+        path: &str,
+        ticks: impl RangeBounds<u64>,
+    ) -> anyhow::Result<usize>
+    where
+        V: Serialize,
+    {
+        self.storage.export_csv(path, frame_bounds(ticks)).await
+    }
+    pub async fn import_csv(&self, path: &str) -> anyhow::Result<usize>
+    where
+        V: DeserializeOwned,
+    {
+        self.storage.import_csv(path).await
+    }
+    pub async fn export_jsonl(
+        &self,
+        path: &str,
+        ticks: impl RangeBounds<u64>,
+    ) -> anyhow::Result<usize>
+    where
+        V: Serialize,
+    {
+        self.storage.export_jsonl(path, frame_bounds(ticks)).await
+    }
+    pub async fn import_jsonl(&self, path: &str) -> anyhow::Result<usize>
+    where
+        V: DeserializeOwned,
+    {
+        self.storage.import_jsonl(path).await
+    }
+    /// Writes the values in `ticks` to a `.npz` archive of parallel `ticks.npy`,
+    /// `channels.npy` and `values.npy` arrays, the last of shape `(rows, *shape)`. The arrays
+    /// are buffered in memory until the scan ends, like `Storage::export_npz`.
+    pub async fn export_npz<E: NpyElement>(
+        &self,
+        path: &str,
+        ticks: impl RangeBounds<u64>,
+        shape: &[usize],
+    ) -> anyhow::Result<usize>
+    where
+        V: Pod,
+    {
+        check_shape::<V, E>(shape)?;
+        let arrays = (
+            npy_buffer(u64::DESCR, &[])?,
+            npy_buffer(u64::DESCR, &[])?,
+            npy_buffer(E::DESCR, shape)?,
+        );
+        let (ticks, channels, values) = self
+            .storage
+            .scan_detached(
+                frame_bounds(ticks),
+                arrays,
+                |(ticks, channels, values), key, value| {
+                    ticks.push(bytemuck::bytes_of(&key.tick))?;
+                    channels.push(bytemuck::bytes_of(&key.channel))?;
+                    values.push(bytemuck::bytes_of(&value))
+                },
+            )
+            .await?;
+        let rows = values.rows();
+        write_npz(
+            path,
+            vec![
+                ("ticks.npy", ticks.finish()?),
+                ("channels.npy", channels.finish()?),
+                ("values.npy", values.finish()?),
+            ],
+        )?;
+        Ok(rows)
+    }
+    pub async fn import_npz<E: NpyElement>(&self, path: &str) -> anyhow::Result<usize>
+    where
+        V: Pod,
+    {
+        let (_, ticks) = read_npz::<u64>(path, "ticks.npy")?;
+        let (_, channels) = read_npz::<u64>(path, "channels.npy")?;
+        let (shape, values) = read_npz::<E>(path, "values.npy")?;
+        let ticks = bytemuck::pod_collect_to_vec::<u8, u64>(&ticks);
+        let channels = bytemuck::pod_collect_to_vec::<u8, u64>(&channels);
+        check_shape::<V, E>(shape.get(1..).unwrap_or_default())?;
+        let size = std::mem::size_of::<V>();
+        anyhow::ensure!(
+            ticks.len() == channels.len() && ticks.len() * size == values.len(),
+            "ticks.npy, channels.npy and values.npy have different lengths."
+        );
+        let entries = ticks
+            .into_iter()
+            .zip(channels)
+            .zip(values.chunks_exact(size.max(1)))
+            .map(|((tick, channel), value)| {
+                (
+                    FrameKey { tick, channel },
+                    bytemuck::pod_read_unaligned::<V>(value),
+                )
+            })
+            .collect::<Vec<_>>();
+        let count = entries.len();
+        self.storage.try_insert_batch(entries).await?;
+        Ok(count)
+    }
+    pub async fn close(self) {
+        self.storage.close().await;
+    }
+}
+impl<T: Pod + Send> Recorder<Vec<T>, PodSliceCodec> {
+    /// Reads `buffer` back from the GPU and records its contents as one channel.
+    pub async fn record_buffer(&self, tick: u64, channel: u64, buffer: &Buffer) {
+        if self.should_record(tick) {
+            self.record(tick, [(channel, buffer.read::<T>().await)]);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use redb::ReadableTable;
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::storage::{Storage, codec::Codec};
+
+const DATA: redb::TableDefinition<'static, String, &[u8]> = redb::TableDefinition::new("data");
+const SCHEMAS: redb::TableDefinition<'static, String, &[u8]> =
+    redb::TableDefinition::new("data.schema");
+
+/// Version and type fingerprint recorded alongside a storage table.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Schema {
+    pub version: u32,
+    pub key_size: usize,
+    pub value_size: usize,
+    /// Full path of the value type, when the fingerprint should also catch renames.
+    pub type_name: Option<String>,
+}
+impl Schema {
+    /// Fingerprints `K` and `V` by their in-memory size only.
+    pub fn of<K, V>(version: u32) -> Self {
+        Self {
+            version,
+            key_size: size_of::<K>(),
+            value_size: size_of::<V>(),
+            type_name: None,
+        }
+    }
+    /// Like `of`, and also records the name of `V`.
+    pub fn named<K, V>(version: u32) -> Self {
+        Self {
+            type_name: Some(std::any::type_name::<V>().to_string()),
+            ..Self::of::<K, V>(version)
+        }
+    }
+    /// The schema stored in the closed database file at `path`, if it has one.
+    pub fn read(path: &str) -> anyhow::Result<Option<Self>> {
+        let db = redb::Database::open(path)?;
+        let txn = db.begin_write()?;
+        let schema = stored(&txn)?;
+        txn.abort()?;
+        Ok(schema)
+    }
+}
+impl std::fmt::Display for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "version {} (key size {}, value size {}",
+            self.version, self.key_size, self.value_size
+        )?;
+        match &self.type_name {
+            Some(name) => write!(f, ", value type {name})"),
+            None => write!(f, ")"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SchemaMismatch {
+    pub path: String,
+    pub stored: Schema,
+    pub requested: Schema,
+}
+impl std::fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Storage at {} holds schema {} but schema {} was requested.",
+            self.path, self.stored, self.requested
+        )
+    }
+}
+impl std::error::Error for SchemaMismatch {}
+
+fn stored(txn: &redb::WriteTransaction) -> anyhow::Result<Option<Schema>> {
+    let table = txn.open_table(SCHEMAS)?;
+    let schema = table.get("data".to_string())?;
+    Ok(schema
+        .map(|bytes| serde_json::from_slice(bytes.value()))
+        .transpose()?)
+}
+
+fn store(txn: &redb::WriteTransaction, schema: &Schema) -> anyhow::Result<()> {
+    let mut table = txn.open_table(SCHEMAS)?;
+    table.insert("data".to_string(), serde_json::to_vec(schema)?.as_slice())?;
+    Ok(())
+}
+
+/// Fails with `SchemaMismatch` unless the stored schema equals `expected`. A table without a
+/// schema, such as one written before versioning, is adopted as `expected`.
+fn check(txn: &redb::WriteTransaction, path: &str, expected: &Schema) -> anyhow::Result<()> {
+    match stored(txn)? {
+        Some(stored) if stored != *expected => Err(SchemaMismatch {
+            path: path.to_string(),
+            stored,
+            requested: expected.clone(),
+        }
+        .into()),
+        Some(_) => Ok(()),
+        None => store(txn, expected),
+    }
+}
+
+/// Fails with `SchemaMismatch` if the database at `path` holds a schema whose fingerprint does
+/// not fit `K` and `V`, whatever its version. Used by the constructors that take no schema.
+pub(crate) fn check_types<K, V>(db: &redb::Database, path: &str) -> anyhow::Result<()> {
+    let txn = db.begin_write()?;
+    let stored = stored(&txn)?;
+    txn.abort()?;
+    let Some(stored) = stored else {
+        return Ok(());
+    };
+    let requested = match stored.type_name {
+        Some(_) => Schema::named::<K, V>(stored.version),
+        None => Schema::of::<K, V>(stored.version),
+    };
+    if stored == requested {
+        return Ok(());
+    }
+    Err(SchemaMismatch {
+        path: path.to_string(),
+        stored,
+        requested,
+    }
+    .into())
+}
+
+impl<K, V, KC, VC> Storage<K, V, KC, VC>
+where
+    K: Send + 'static,
+    V: Send + 'static,
+    KC: Codec<K>,
+    VC: Codec<V>,
+{
+    /// Like `new`, but refuses a database whose stored schema differs from `schema`.
+    pub fn open(path: &str, schema: Schema) -> anyhow::Result<Self> {
+        let db = redb::Database::create(path)?;
+        let txn = db.begin_write()?;
+        check(&txn, path, &schema)?;
+        txn.commit()?;
+        Ok(Self::spawn(db, Some(path.to_string())))
+    }
+    /// Rewrites every value of the database at `path` from schema `from` to schema `to`, which
+    /// must be the next version, in a single write transaction. Secondary indexes are rebuilt
+    /// the next time they are added.
+    pub fn migrate<V0, VC0: Codec<V0>>(
+        path: &str,
+        from: &Schema,
+        to: &Schema,
+        mut rewrite: impl FnMut(V0) -> anyhow::Result<V>,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            to.version == from.version + 1,
+            "Cannot migrate storage from version {} to version {}.",
+            from.version,
+            to.version
+        );
+        let db = redb::Database::open(path)?;
+        let txn = db.begin_write()?;
+        check(&txn, path, from)?;
+        {
+            let mut table = txn.open_table(DATA)?;
+            let rewritten = table
+                .iter()?
+                .map(|entry| {
+                    let (k, v) = entry?;
+                    let value = rewrite(VC0::decode(v.value())?)?;
+                    Ok((k.value(), VC::encode(&value)))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            for (k, v) in rewritten {
+                table.insert(k, v.as_slice())?;
+            }
+        }
+        store(&txn, to)?;
+        txn.commit()?;
+        Ok(())
+    }
+}
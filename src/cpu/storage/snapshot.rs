@@ -0,0 +1,104 @@
+use std::{marker::PhantomData, ops::RangeBounds, sync::Arc};
+
+use redb::ReadableTableMetadata;
+
+use crate::cpu::storage::codec::Codec;
+
+pub(crate) type DataTable = redb::ReadOnlyTable<String, &'static [u8]>;
+
+/// Consistent read-only view of a storage table as of the moment it was taken, returned by
+/// `Storage::read_snapshot`. Reads run on the calling thread against a redb read transaction,
+/// so clones can be shared across threads while the worker keeps committing writes.
+pub struct ReadSnapshot<K, V, KC, VC> {
+    table: Arc<Option<DataTable>>,
+    types: PhantomData<(K, V, KC, VC)>,
+}
+impl<K, V, KC, VC> Clone for ReadSnapshot<K, V, KC, VC> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            types: PhantomData,
+        }
+    }
+}
+impl<K, V, KC: Codec<K>, VC: Codec<V>> ReadSnapshot<K, V, KC, VC> {
+    pub(crate) fn new(table: Option<DataTable>) -> Self {
+        Self {
+            table: Arc::new(table),
+            types: PhantomData,
+        }
+    }
+    pub fn get(&self, key: &K) -> anyhow::Result<Option<V>> {
+        self.view(key, |bytes| bytes.map(VC::decode).transpose())?
+    }
+    /// Runs `f` against the raw stored bytes, without an intermediate copy.
+    pub fn view<R>(&self, key: &K, f: impl FnOnce(Option<&[u8]>) -> R) -> anyhow::Result<R> {
+        let Some(table) = self.table.as_ref() else {
+            return Ok(f(None));
+        };
+        let guard = table.get(hex::encode(KC::encode(key)))?;
+        Ok(f(guard.as_ref().map(|b| b.value())))
+    }
+    /// Returns the entries whose encoded keys fall inside `range`, in encoded byte order.
+    pub fn range(&self, range: impl RangeBounds<K>) -> anyhow::Result<Vec<(K, V)>> {
+        let Some(table) = self.table.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let start = range.start_bound().map(|k| hex::encode(KC::encode(k)));
+        let end = range.end_bound().map(|k| hex::encode(KC::encode(k)));
+        table
+            .range::<String>((start, end))?
+            .map(|entry| {
+                let (k, v) = entry?;
+                Ok((
+                    KC::decode(&hex::decode(k.value())?)?,
+                    VC::decode(v.value())?,
+                ))
+            })
+            .collect()
+    }
+    /// Feeds the entries inside `range` to `visit` one at a time, without collecting them first.
+    pub fn scan<S>(
+        &self,
+        range: impl RangeBounds<K>,
+        mut state: S,
+        mut visit: impl FnMut(&mut S, K, V) -> anyhow::Result<()>,
+    ) -> anyhow::Result<S> {
+        let Some(table) = self.table.as_ref() else {
+            return Ok(state);
+        };
+        let start = range.start_bound().map(|k| hex::encode(KC::encode(k)));
+        let end = range.end_bound().map(|k| hex::encode(KC::encode(k)));
+        for entry in table.range::<String>((start, end))? {
+            let (k, v) = entry?;
+            visit(
+                &mut state,
+                KC::decode(&hex::decode(k.value())?)?,
+                VC::decode(v.value())?,
+            )?;
+        }
+        Ok(state)
+    }
+    /// The first key inside `range`, without decoding its value.
+    pub fn first_key(&self, range: impl RangeBounds<K>) -> anyhow::Result<Option<K>> {
+        let Some(table) = self.table.as_ref() else {
+            return Ok(None);
+        };
+        let start = range.start_bound().map(|k| hex::encode(KC::encode(k)));
+        let end = range.end_bound().map(|k| hex::encode(KC::encode(k)));
+        match table.range::<String>((start, end))?.next() {
+            Some(entry) => Ok(Some(KC::decode(&hex::decode(entry?.0.value())?)?)),
+            None => Ok(None),
+        }
+    }
+    /// Number of entries in the table.
+    pub fn len(&self) -> anyhow::Result<u64> {
+        match self.table.as_ref() {
+            Some(table) => Ok(table.len()?),
+            None => Ok(0),
+        }
+    }
+    pub fn is_empty(&self) -> anyhow::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
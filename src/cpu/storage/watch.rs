@@ -0,0 +1,129 @@
+use std::marker::PhantomData;
+
+use tokio::sync::broadcast;
+
+use crate::cpu::storage::codec::Codec;
+
+/// A committed change to a watched key.
+#[derive(Clone, PartialEq, Debug)]
+pub enum StorageEvent<K, V> {
+    Insert { key: K, value: V },
+    Remove { key: K },
+}
+
+/// What a watcher does when it falls more than its capacity behind the worker.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Overflow {
+    /// Silently skip the oldest buffered events and carry on.
+    DropOldest,
+    /// Report a `Lagged` error with the number of events lost, then carry on.
+    Lag,
+}
+
+#[derive(Debug)]
+pub struct Lagged(pub u64);
+impl std::fmt::Display for Lagged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Storage watcher lagged behind and missed {} events.",
+            self.0
+        )
+    }
+}
+impl std::error::Error for Lagged {}
+
+/// Event as encoded by the worker, decoded on the watcher's side.
+#[derive(Clone, Debug)]
+pub struct RawEvent {
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+}
+
+pub enum WatchFilter {
+    Key(Vec<u8>),
+    Prefix(Vec<u8>),
+}
+impl WatchFilter {
+    fn matches(&self, key: &[u8]) -> bool {
+        match self {
+            WatchFilter::Key(k) => k == key,
+            WatchFilter::Prefix(p) => key.starts_with(p),
+        }
+    }
+}
+
+/// Watchers registered with a storage worker.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    inner: Vec<(WatchFilter, broadcast::Sender<RawEvent>)>,
+}
+impl Subscribers {
+    pub(crate) fn add(&mut self, filter: WatchFilter, sender: broadcast::Sender<RawEvent>) {
+        self.inner.push((filter, sender));
+    }
+    /// Sends the events of a committed transaction, forgetting watchers that were dropped.
+    pub(crate) fn publish(&mut self, events: Vec<RawEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        self.inner.retain(|(filter, sender)| {
+            events
+                .iter()
+                .filter(|e| filter.matches(&e.key))
+                .all(|e| sender.send(e.clone()).is_ok())
+        });
+    }
+}
+
+/// Stream of committed changes, returned by `Storage::watch` and `Storage::watch_prefix`.
+pub struct Watch<K, V, KC, VC> {
+    receiver: broadcast::Receiver<RawEvent>,
+    overflow: Overflow,
+    types: PhantomData<(K, V, KC, VC)>,
+}
+impl<K, V, KC: Codec<K>, VC: Codec<V>> Watch<K, V, KC, VC> {
+    pub(crate) fn new(receiver: broadcast::Receiver<RawEvent>, overflow: Overflow) -> Self {
+        Self {
+            receiver,
+            overflow,
+            types: PhantomData,
+        }
+    }
+    /// Waits for the next event. Returns `None` once the storage has been closed.
+    pub async fn recv(&mut self) -> Option<anyhow::Result<StorageEvent<K, V>>> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(Self::decode(event)),
+                Err(broadcast::error::RecvError::Closed) => return None,
+                Err(broadcast::error::RecvError::Lagged(n)) => match self.overflow {
+                    Overflow::DropOldest => continue,
+                    Overflow::Lag => return Some(Err(Lagged(n).into())),
+                },
+            }
+        }
+    }
+    /// Returns an already published event without waiting, if there is one.
+    pub fn try_recv(&mut self) -> Option<anyhow::Result<StorageEvent<K, V>>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => return Some(Self::decode(event)),
+                Err(broadcast::error::TryRecvError::Lagged(n)) => match self.overflow {
+                    Overflow::DropOldest => continue,
+                    Overflow::Lag => return Some(Err(Lagged(n).into())),
+                },
+                Err(_) => return None,
+            }
+        }
+    }
+    fn decode(event: RawEvent) -> anyhow::Result<StorageEvent<K, V>> {
+        let key = KC::decode(&event.key)?;
+        Ok(match event.value {
+            Some(value) => StorageEvent::Insert {
+                key,
+                value: VC::decode(&value)?,
+            },
+            None => StorageEvent::Remove { key },
+        })
+    }
+}
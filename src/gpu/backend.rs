@@ -17,7 +17,6 @@ impl Backend {
                 power_preference: wgpu::PowerPreference::HighPerformance,
                 compatible_surface: None,
                 force_fallback_adapter: false,
-                ..Default::default()
             })
             .await
             .unwrap();
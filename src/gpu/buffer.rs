@@ -19,7 +19,7 @@ pub enum BufferRole {
 }
 impl BufferRole {
     pub fn to_usage(&self) -> wgpu::BufferUsages {
-        return match self {
+        match self {
             BufferRole::Storage => {
                 wgpu::BufferUsages::STORAGE
                     | wgpu::BufferUsages::COPY_DST
@@ -32,7 +32,7 @@ impl BufferRole {
             BufferRole::StagingWrite => {
                 wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC
             }
-        };
+        }
     }
 }
 
@@ -55,7 +55,7 @@ impl Buffer {
         let inner = backend_lock.device.create_buffer(&BufferDescriptor {
             label: None,
             usage: role.to_usage(),
-            size: size,
+            size,
             mapped_at_creation: false,
         });
         drop(backend_lock);
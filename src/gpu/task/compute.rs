@@ -2,49 +2,49 @@ use std::{fs, sync::Arc};
 use tokio::sync::Mutex;
 use wgpu::CommandEncoderDescriptor;
 
-use crate::gpu::{
-    backend::Backend,
-    buffer::{Buffer, BufferRole},
+use crate::{
+    gpu::{
+        backend::Backend,
+        buffer::{Buffer, BufferRole},
+    },
+    reload::Replacement,
 };
 
-pub struct ComputeTask {
-    backend: Arc<Mutex<Backend>>,
+/// The parts of a `ComputeTask` built from its shader.
+#[derive(Clone)]
+struct Pipeline {
     pipeline: Arc<wgpu::ComputePipeline>,
     bind_group: Arc<wgpu::BindGroup>,
-    dispatches: (u32, u32, u32),
-    input_buffers: Vec<Arc<Buffer>>,
-    output_buffers: Vec<Arc<Buffer>>,
 }
-impl ComputeTask {
-    pub async fn new(
-        backend: Arc<Mutex<Backend>>,
+impl Pipeline {
+    /// Builds the pipeline from the WGSL source of the shader at `path`, binding the buffers in
+    /// order. Fails on shader or binding errors instead of raising them as uncaptured device
+    /// errors.
+    async fn build(
+        backend: &Backend,
         path: &str,
-        input_buffers: Vec<Arc<Buffer>>,
-        output_buffers: Vec<Arc<Buffer>>,
-        dispatches: (u32, u32, u32),
-    ) -> Self {
-        let backend_lock = backend.lock().await;
-        let shader_code = fs::read_to_string(path).expect("Could not find/read shader path.");
-        let shader = backend_lock
+        shader_code: &str,
+        buffers: &[Arc<Buffer>],
+    ) -> anyhow::Result<Self> {
+        let scope = backend
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = backend
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: None,
                 source: wgpu::ShaderSource::Wgsl(shader_code.into()),
             });
-        let pipeline =
-            backend_lock
-                .device
-                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                    label: None,
-                    layout: None,
-                    module: &shader,
-                    entry_point: None,
-                    compilation_options: Default::default(),
-                    cache: Default::default(),
-                });
-        let mut buffers = Vec::new();
-        buffers.extend(input_buffers.clone());
-        buffers.extend(output_buffers.clone());
+        let pipeline = backend
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: None,
+                layout: None,
+                module: &shader,
+                entry_point: None,
+                compilation_options: Default::default(),
+                cache: Default::default(),
+            });
         let bind_group_entries = buffers
             .iter()
             .enumerate()
@@ -53,22 +53,66 @@ impl ComputeTask {
                 resource: b.inner.as_entire_binding(),
             })
             .collect::<Vec<_>>();
-        let bind_group = backend_lock
+        let bind_group = backend
             .device
             .create_bind_group(&wgpu::BindGroupDescriptor {
                 label: None,
                 layout: &pipeline.get_bind_group_layout(0),
                 entries: &bind_group_entries,
             });
-        Self {
-            backend: backend.clone(),
+        if let Some(error) = scope.pop().await {
+            anyhow::bail!("Could not build compute pipeline from {path}: {error}");
+        }
+        Ok(Self {
             pipeline: pipeline.into(),
             bind_group: bind_group.into(),
+        })
+    }
+}
+
+pub struct ComputeTask {
+    backend: Arc<Mutex<Backend>>,
+    path: String,
+    pipeline: Arc<std::sync::Mutex<Pipeline>>,
+    dispatches: (u32, u32, u32),
+    input_buffers: Vec<Arc<Buffer>>,
+    output_buffers: Vec<Arc<Buffer>>,
+}
+impl ComputeTask {
+    pub async fn new(
+        backend: Arc<Mutex<Backend>>,
+        path: &str,
+        input_buffers: Vec<Arc<Buffer>>,
+        output_buffers: Vec<Arc<Buffer>>,
+        dispatches: (u32, u32, u32),
+    ) -> Self {
+        let mut buffers = Vec::new();
+        buffers.extend(input_buffers.clone());
+        buffers.extend(output_buffers.clone());
+        let shader_code = fs::read_to_string(path).expect("Could not read shader.");
+        let pipeline = Pipeline::build(&*backend.lock().await, path, &shader_code, &buffers)
+            .await
+            .expect("Could not build compute pipeline.");
+        Self {
+            backend: backend.clone(),
+            path: path.to_string(),
+            pipeline: Arc::new(std::sync::Mutex::new(pipeline)),
             dispatches,
             input_buffers,
             output_buffers,
         }
     }
+    /// A handle for rebuilding the pipeline from the shader path, even while the task is in use.
+    pub fn reloader(&self) -> ShaderReloader {
+        let mut buffers = self.input_buffers.clone();
+        buffers.extend(self.output_buffers.clone());
+        ShaderReloader {
+            backend: self.backend.clone(),
+            path: self.path.clone(),
+            buffers,
+            pipeline: self.pipeline.clone(),
+        }
+    }
     pub async fn execute(&self) {
         let mut temp_buffers = Vec::new();
         for (i, b) in self.output_buffers.iter().enumerate() {
@@ -109,10 +153,42 @@ impl ComputeTask {
                 label: None,
                 ..Default::default()
             });
-            cpass.set_pipeline(&self.pipeline);
-            cpass.set_bind_group(0, &*self.bind_group.clone(), &[]);
+            let Pipeline {
+                pipeline,
+                bind_group,
+            } = self
+                .pipeline
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
+            cpass.set_pipeline(&pipeline);
+            cpass.set_bind_group(0, &*bind_group, &[]);
             cpass.dispatch_workgroups(self.dispatches.0, self.dispatches.1, self.dispatches.2);
         };
         queue.submit([encoder.finish()]);
     }
 }
+
+/// Rebuilds the pipeline of a `ComputeTask`.
+#[derive(Clone)]
+pub struct ShaderReloader {
+    backend: Arc<Mutex<Backend>>,
+    path: String,
+    buffers: Vec<Arc<Buffer>>,
+    pipeline: Arc<std::sync::Mutex<Pipeline>>,
+}
+impl ShaderReloader {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Recompiles the shader from `source`, the new contents of its file. The current pipeline
+    /// stays in use until the returned replacement is applied, and for good if building fails.
+    pub async fn reload(&self, source: &str) -> anyhow::Result<Replacement> {
+        let backend = self.backend.lock().await;
+        let pipeline = Pipeline::build(&backend, &self.path, source, &self.buffers).await?;
+        let target = self.pipeline.clone();
+        Ok(Replacement::new(move || {
+            *target.lock().unwrap_or_else(|e| e.into_inner()) = pipeline;
+        }))
+    }
+}
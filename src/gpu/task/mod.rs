@@ -9,9 +9,9 @@ pub enum TaskID {
     Compute(NodeIndex),
     Graphics(NodeIndex),
 }
-impl Into<NodeIndex> for TaskID {
-    fn into(self) -> NodeIndex {
-        match self {
+impl From<TaskID> for NodeIndex {
+    fn from(id: TaskID) -> Self {
+        match id {
             TaskID::Compute(id) => id,
             TaskID::Graphics(id) => id,
         }
@@ -23,6 +23,7 @@ pub enum Task {
     Graphics(GraphicsTask),
 }
 
+#[derive(Default)]
 pub struct TaskManager {
     task_graph: DiGraph<Task, ()>,
 }
@@ -51,7 +51,6 @@ impl Texture {
             width: size[0],
             height: size[1],
             depth_or_array_layers: size[2],
-            ..Default::default()
         };
         let device = backend.lock().await.device.clone();
         let inner = device.create_texture(&wgpu::TextureDescriptor {
@@ -77,8 +76,8 @@ impl Texture {
     pub async fn read<T: Pod>(&self) -> Vec<T> {
         let device = self.backend.lock().await.device.clone();
         let bytes_per_pixel = std::mem::size_of::<T>() as u32;
-        let bytes_per_row = (self.size.width * bytes_per_pixel + COPY_BYTES_PER_ROW_ALIGNMENT - 1)
-            / COPY_BYTES_PER_ROW_ALIGNMENT
+        let bytes_per_row = (self.size.width * bytes_per_pixel)
+            .div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT)
             * COPY_BYTES_PER_ROW_ALIGNMENT;
         let staging = Buffer::new_empty::<T>(
             self.backend.clone(),
@@ -124,7 +123,7 @@ impl Texture {
             let row_bytes = &mapped[row_start..row_end];
             let row_t = row_bytes
                 .chunks_exact(bytes_per_pixel as usize)
-                .map(|chunk| -> T { bytemuck::from_bytes::<T>(chunk).clone() })
+                .map(|chunk| -> T { *bytemuck::from_bytes::<T>(chunk) })
                 .collect::<Vec<T>>();
             result.extend_from_slice(&row_t);
         }
@@ -134,8 +133,8 @@ impl Texture {
     }
     pub async fn write<T: Pod>(&self, data: &[T]) {
         let bytes_per_pixel = std::mem::size_of::<T>() as u32;
-        let bytes_per_row = (self.size.width * bytes_per_pixel + COPY_BYTES_PER_ROW_ALIGNMENT - 1)
-            / COPY_BYTES_PER_ROW_ALIGNMENT
+        let bytes_per_row = (self.size.width * bytes_per_pixel)
+            .div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT)
             * COPY_BYTES_PER_ROW_ALIGNMENT;
         let mut staging_bytes = vec![0u8; (bytes_per_row * self.size.height) as usize];
 
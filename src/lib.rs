@@ -1,3 +1,4 @@
-#![feature(portable_simd, oneshot_channel, fn_traits, trait_alias)]
+#![feature(portable_simd, oneshot_channel, trait_alias)]
 pub mod cpu;
 pub mod gpu;
+pub mod reload;
@@ -0,0 +1,101 @@
+use std::{fs, time::SystemTime};
+
+use crate::{
+    cpu::simulation::tecs::{TECS, script::ScriptReloader},
+    gpu::task::compute::ShaderReloader,
+};
+
+enum Target {
+    Script(ScriptReloader),
+    Shader(ShaderReloader),
+}
+
+struct Watched {
+    path: String,
+    /// Modification time and length when last seen.
+    stamp: Option<(SystemTime, u64)>,
+    target: Target,
+}
+
+/// Outcome of rebuilding something from a changed file.
+#[derive(Debug)]
+pub struct Reloaded {
+    pub path: String,
+    /// Fails if the new version didn't build, in which case the previous one stays in use.
+    pub result: anyhow::Result<()>,
+}
+
+/// A rebuilt script function or shader pipeline, waiting to replace the one in use.
+pub struct Replacement(Box<dyn FnOnce() + Send>);
+impl Replacement {
+    pub(crate) fn new(swap: impl FnOnce() + Send + 'static) -> Self {
+        Self(Box::new(swap))
+    }
+    /// Puts the new version in use from the next run or execution.
+    pub fn apply(self) {
+        (self.0)()
+    }
+}
+
+/// Rebuilds scripts and shaders when their files change. Files are polled rather than
+/// watched through OS notifications, so call `poll` periodically.
+#[derive(Default)]
+pub struct Reloader {
+    watched: Vec<Watched>,
+}
+impl Reloader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Reloads a script system's function from `path` when it changes.
+    pub fn watch_script(&mut self, path: &str, script: ScriptReloader) {
+        self.watch(path, Target::Script(script));
+    }
+    /// Rebuilds a compute task's pipeline when its shader changes.
+    pub fn watch_shader(&mut self, shader: ShaderReloader) {
+        let path = shader.path().to_string();
+        self.watch(&path, Target::Shader(shader));
+    }
+    fn watch(&mut self, path: &str, target: Target) {
+        self.watched.push(Watched {
+            path: path.to_string(),
+            stamp: stamp(path),
+            target,
+        });
+    }
+    /// Rebuilds everything whose file changed since the last poll. The new versions are swapped
+    /// in together on the thread of `tecs` before its next tick, so no tick mixes old and new.
+    /// Files that can't be read, as while an editor replaces them, are picked up on a later
+    /// poll.
+    pub async fn poll(&mut self, tecs: &TECS) -> Vec<Reloaded> {
+        let mut reloaded = Vec::new();
+        let mut replacements = Vec::new();
+        for watched in &mut self.watched {
+            let stamp = stamp(&watched.path);
+            if stamp.is_none() || stamp == watched.stamp {
+                continue;
+            }
+            let Ok(source) = fs::read_to_string(&watched.path) else {
+                continue;
+            };
+            watched.stamp = stamp;
+            let result = match &watched.target {
+                Target::Script(script) => script.reload(&source),
+                Target::Shader(shader) => shader.reload(&source).await,
+            };
+            reloaded.push(Reloaded {
+                path: watched.path.clone(),
+                result: result.map(|replacement| replacements.push(replacement)),
+            });
+        }
+        if !replacements.is_empty() {
+            tecs.between_ticks(move |_| replacements.into_iter().for_each(Replacement::apply));
+        }
+        reloaded
+    }
+}
+
+fn stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -1,11 +1,14 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use quadrax::cpu::simulation::tecs::TECS;
 use quadrax::gpu::task::compute::ComputeTask;
 use quadrax::gpu::{
     backend::Backend,
     buffer::{Buffer, BufferRole},
 };
+use quadrax::reload::Reloader;
+use tempfile::TempDir;
 
 #[tokio::test]
 async fn add_one() {
@@ -35,3 +38,59 @@ async fn add_one() {
     let expected: Vec<u32> = input_data.iter().map(|v| v + 1).collect();
     assert_eq!(result, expected);
 }
+
+#[tokio::test]
+async fn reload_shader() {
+    let backend = Backend::new().await.arc_mutex();
+    let input_data: Vec<u32> = vec![0, 1, 2, 3, 4];
+    let input_buffer =
+        Arc::new(Buffer::new(backend.clone(), input_data.clone(), BufferRole::Storage).await);
+    let output_buffer = Arc::new(
+        Buffer::new_empty::<u32>(
+            backend.clone(),
+            (input_data.len() * 4) as u64,
+            BufferRole::Storage,
+        )
+        .await,
+    );
+    let tmp_dir = TempDir::new().unwrap();
+    let shader_path = tmp_dir.path().join("compute.wgsl");
+    let original = std::fs::read_to_string(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/shaders/compute.wgsl"),
+    )
+    .unwrap();
+    std::fs::write(&shader_path, &original).unwrap();
+    let task = ComputeTask::new(
+        backend.clone(),
+        shader_path.to_str().unwrap(),
+        vec![input_buffer.clone()],
+        vec![output_buffer.clone()],
+        (input_data.len() as u32, 1, 1),
+    )
+    .await;
+    let mut reloader = Reloader::new();
+    reloader.watch_shader(task.reloader());
+    let tecs = TECS::new();
+
+    let rewrite = |contents: &str, age: u64| {
+        std::fs::write(&shader_path, contents).unwrap();
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&shader_path)
+            .unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(age))
+            .unwrap();
+    };
+    rewrite(&original.replace("+ 1", "+ 2"), 1);
+    assert!(reloader.poll(&tecs).await[0].result.is_ok());
+    tecs.tick().await.unwrap();
+    task.execute().await;
+    let expected: Vec<u32> = input_data.iter().map(|v| v + 2).collect();
+    assert_eq!(output_buffer.read::<u32>().await, expected);
+
+    rewrite("@compute fn main(", 2);
+    assert!(reloader.poll(&tecs).await[0].result.is_err());
+    tecs.tick().await.unwrap();
+    task.execute().await;
+    assert_eq!(output_buffer.read::<u32>().await, expected);
+}
@@ -0,0 +1,189 @@
+use quadrax::cpu::storage::{
+    Storage,
+    codec::{JsonCodec, OrderedCodec},
+    recorder::Recorder,
+};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+struct Body {
+    name: String,
+    position: [f32; 3],
+    tags: Option<String>,
+}
+
+fn body(i: u32) -> Body {
+    Body {
+        name: format!("body \"{i}\", {i}"),
+        position: [i as f32, 0.5, -2.0],
+        tags: i.is_multiple_of(2).then(|| format!("{i}")),
+    }
+}
+
+#[tokio::test]
+async fn csv_and_jsonl_round_trip() {
+    let tmp_dir = TempDir::new().unwrap();
+    let csv_path = tmp_dir.path().join("bodies.csv");
+    let jsonl_path = tmp_dir.path().join("bodies.jsonl");
+    let source: Storage<u32, Body, OrderedCodec, JsonCodec> = Storage::in_memory();
+    for i in 0..10 {
+        source.insert(i, body(i)).await;
+    }
+
+    let csv = csv_path.to_str().unwrap();
+    assert_eq!(source.export_csv(csv, 2..8).await.unwrap(), 6);
+    let text = std::fs::read_to_string(csv).unwrap();
+    assert_eq!(
+        text.lines().next().unwrap(),
+        "key,value.name,value.position.0,value.position.1,value.position.2,value.tags"
+    );
+    assert_eq!(
+        text.lines().nth(1).unwrap(),
+        r#"2,"body ""2"", 2",2.0,0.5,-2.0,"2""#
+    );
+    let jsonl = jsonl_path.to_str().unwrap();
+    assert_eq!(source.export_jsonl(jsonl, ..).await.unwrap(), 10);
+
+    let from_csv: Storage<u32, Body, OrderedCodec, JsonCodec> = Storage::in_memory();
+    assert_eq!(from_csv.import_csv(csv).await.unwrap(), 6);
+    assert_eq!(
+        from_csv.range(..).await.unwrap(),
+        source.range(2..8).await.unwrap()
+    );
+    let from_jsonl: Storage<u32, Body, OrderedCodec, JsonCodec> = Storage::in_memory();
+    assert_eq!(from_jsonl.import_jsonl(jsonl).await.unwrap(), 10);
+    assert_eq!(
+        from_jsonl.range(..).await.unwrap(),
+        source.range(..).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn npy_and_npz_round_trip() {
+    let tmp_dir = TempDir::new().unwrap();
+    let npy_path = tmp_dir.path().join("positions.npy");
+    let npz_path = tmp_dir.path().join("positions.npz");
+    let source: Storage<u32, [f32; 3], OrderedCodec> = Storage::in_memory();
+    for i in 0..1500 {
+        source.insert(i, [i as f32, 1.0, 2.0]).await;
+    }
+
+    let npy = npy_path.to_str().unwrap();
+    assert_eq!(source.export_npy::<f32>(npy, .., &[3]).await.unwrap(), 1500);
+    let bytes = std::fs::read(npy).unwrap();
+    assert_eq!(&bytes[..6], b"\x93NUMPY");
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    assert!((10 + header_len).is_multiple_of(64));
+    let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+    assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (1500, 3), }"));
+    assert_eq!(bytes.len(), 10 + header_len + 1500 * 12);
+    assert!(source.export_npy::<f64>(npy, .., &[3]).await.is_err());
+
+    let from_npy: Storage<u32, [f32; 3], OrderedCodec> = Storage::in_memory();
+    let imported = from_npy.import_npy::<f32>(npy, |i| i as u32 * 2).await;
+    assert_eq!(imported.unwrap(), 1500);
+    assert_eq!(from_npy.get(20).await, Some([10.0, 1.0, 2.0]));
+
+    let npz = npz_path.to_str().unwrap();
+    assert_eq!(
+        source.export_npz::<f32>(npz, 10..20, &[3]).await.unwrap(),
+        10
+    );
+    let from_npz: Storage<u32, [f32; 3], OrderedCodec> = Storage::in_memory();
+    assert_eq!(from_npz.import_npz::<f32>(npz).await.unwrap(), 10);
+    assert_eq!(
+        from_npz.range(..).await.unwrap(),
+        source.range(10..20).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn csv_columns_follow_field_order() {
+    #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+    struct Sample {
+        velocity: [f32; 2],
+        mass: f32,
+        charge: i32,
+    }
+    let tmp_dir = TempDir::new().unwrap();
+    let csv_path = tmp_dir.path().join("samples.csv");
+    let csv = csv_path.to_str().unwrap();
+    let source: Storage<u32, Sample, OrderedCodec, JsonCodec> = Storage::in_memory();
+    let sample = Sample {
+        velocity: [1.0, -1.0],
+        mass: 2.5,
+        charge: -1,
+    };
+    source.insert(7, sample.clone()).await;
+
+    assert_eq!(source.export_csv(csv, ..).await.unwrap(), 1);
+    assert_eq!(
+        std::fs::read_to_string(csv).unwrap(),
+        "key,value.velocity.0,value.velocity.1,value.mass,value.charge\n7,1.0,-1.0,2.5,-1\n"
+    );
+    let imported: Storage<u32, Sample, OrderedCodec, JsonCodec> = Storage::in_memory();
+    assert_eq!(imported.import_csv(csv).await.unwrap(), 1);
+    assert_eq!(imported.get(7).await, Some(sample));
+}
+
+#[tokio::test]
+async fn recording_export_round_trip() {
+    let tmp_dir = TempDir::new().unwrap();
+    let npz_path = tmp_dir.path().join("recording.npz");
+    let csv_path = tmp_dir.path().join("recording.csv");
+    let recorder: Recorder<[f32; 2]> = Recorder::in_memory(1, None);
+    for tick in 0..20u64 {
+        recorder.record(tick, (0..3).map(|c| (c, [tick as f32, c as f32])));
+    }
+
+    let npz = npz_path.to_str().unwrap();
+    assert_eq!(
+        recorder.export_npz::<f32>(npz, 5..10, &[2]).await.unwrap(),
+        15
+    );
+    let csv = csv_path.to_str().unwrap();
+    assert_eq!(recorder.export_csv(csv, 5..10).await.unwrap(), 15);
+    let header = std::fs::read_to_string(csv).unwrap();
+    assert!(header.starts_with("key.tick,key.channel,value.0,value.1\n5,0,5.0,0.0\n"));
+
+    let expected = recorder.frames(5..10).await.unwrap().collect::<Vec<_>>();
+    let from_npz: Recorder<[f32; 2]> = Recorder::in_memory(1, None);
+    assert_eq!(from_npz.import_npz::<f32>(npz).await.unwrap(), 15);
+    assert_eq!(
+        from_npz.frames(..).await.unwrap().collect::<Vec<_>>(),
+        expected
+    );
+    let from_csv: Recorder<[f32; 2]> = Recorder::in_memory(1, None);
+    assert_eq!(from_csv.import_csv(csv).await.unwrap(), 15);
+    assert_eq!(
+        from_csv.frames(..).await.unwrap().collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[tokio::test]
+async fn imports_report_failed_writes() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("indexed.redb");
+    let db = db_path.to_str().unwrap();
+    let jsonl_path = tmp_dir.path().join("bodies.jsonl");
+    let jsonl = jsonl_path.to_str().unwrap();
+    let storage: Storage<u32, Body, OrderedCodec, JsonCodec> = Storage::new(db);
+    storage
+        .add_index::<String, JsonCodec>("name", |b| b.name.clone())
+        .await
+        .unwrap();
+    storage.insert(1, body(1)).await;
+    assert_eq!(storage.export_jsonl(jsonl, ..).await.unwrap(), 1);
+    storage.close().await;
+
+    // The reopened store refuses writes until its index is added again.
+    let storage: Storage<u32, Body, OrderedCodec, JsonCodec> = Storage::new(db);
+    let error = storage.import_jsonl(jsonl).await.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Index name must be added again before writing to this storage."
+    );
+    storage.close().await;
+}
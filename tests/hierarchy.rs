@@ -0,0 +1,99 @@
+use quadrax::cpu::{
+    maths::matrix::Matrix,
+    simulation::tecs::{
+        EntityID, TECS,
+        hierarchy::{GlobalTransform, LocalTransform, transform_propagation},
+    },
+};
+
+fn translation(x: f32, y: f32, z: f32) -> LocalTransform {
+    LocalTransform(Matrix::new([
+        [1., 0., 0., x],
+        [0., 1., 0., y],
+        [0., 0., 1., z],
+        [0., 0., 0., 1.],
+    ]))
+}
+
+fn position(tecs: &TECS, entity: EntityID) -> [f32; 3] {
+    let (GlobalTransform(global),) = tecs.fetch::<(GlobalTransform,)>(entity).unwrap();
+    std::array::from_fn(|i| global.row(i)[3])
+}
+
+#[test]
+fn transforms_propagate_down_deep_hierarchies() {
+    let tecs = TECS::new();
+    tecs.add_systems(vec![transform_propagation()]).unwrap();
+    let root = tecs.create_entity((translation(0., 0., 5.),));
+    let mut chain = vec![root];
+    for _ in 0..1000 {
+        let link = tecs.create_entity((translation(1., 0., 0.),));
+        tecs.set_parent(link, *chain.last().unwrap()).unwrap();
+        chain.push(link);
+    }
+    tecs.tick().wait().unwrap();
+    for (depth, &link) in chain.iter().enumerate().step_by(100) {
+        assert_eq!(position(&tecs, link), [depth as f32, 0., 5.]);
+    }
+
+    let (descendants, ancestors) =
+        tecs.with_world(move |world| (world.descendants(root), world.ancestors(chain[1000])));
+    assert_eq!(descendants.len(), 1000);
+    assert_eq!(ancestors.len(), 1000);
+    assert_eq!((descendants[0], ancestors[999]), (ancestors[998], root));
+
+    // Moving the root moves everything below it.
+    tecs.insert_components(root, (translation(0., 2., 0.),))
+        .unwrap();
+    tecs.tick().wait().unwrap();
+    assert_eq!(position(&tecs, descendants[999]), [1000., 2., 0.]);
+
+    tecs.remove_entity_recursive(descendants[499]).unwrap();
+    assert!(!tecs.contains(descendants[999]));
+    assert!(tecs.contains(descendants[498]));
+    let children = tecs.with_world(move |world| world.children(descendants[498]));
+    assert!(children.is_empty());
+}
+
+#[test]
+fn reparenting_keeps_both_sides_consistent() {
+    let tecs = TECS::new();
+    tecs.add_systems(vec![transform_propagation()]).unwrap();
+    let a = tecs.create_entity((translation(10., 0., 0.),));
+    let b = tecs.create_entity((translation(0., 5., 0.),));
+    let c = tecs.create_entity((translation(1., 0., 0.),));
+    let d = tecs.create_entity((translation(0., 0., 1.),));
+    tecs.set_parent(c, a).unwrap();
+    tecs.set_parent(d, c).unwrap();
+    tecs.tick().wait().unwrap();
+    assert_eq!(position(&tecs, d), [11., 0., 1.]);
+
+    tecs.set_parent(c, b).unwrap();
+    tecs.tick().wait().unwrap();
+    assert_eq!(position(&tecs, c), [1., 5., 0.]);
+    assert_eq!(position(&tecs, d), [1., 5., 1.]);
+    let (a_children, b_children, c_parent) =
+        tecs.with_world(move |world| (world.children(a), world.children(b), world.parent(c)));
+    assert_eq!(
+        (a_children, b_children, c_parent),
+        (vec![], vec![c], Some(b))
+    );
+
+    // Cycles are refused and leave the hierarchy as it was.
+    assert!(tecs.set_parent(b, d).is_err());
+    assert!(tecs.set_parent(b, b).is_err());
+    assert_eq!(tecs.with_world(move |world| world.ancestors(d)), [c, b]);
+
+    // Despawning a parent on its own leaves its children as roots.
+    tecs.remove_entity(c);
+    tecs.tick().wait().unwrap();
+    let (b_children, d_parent) = tecs.with_world(move |world| (world.children(b), world.parent(d)));
+    assert_eq!((b_children, d_parent), (vec![], None));
+    assert_eq!(position(&tecs, d), [0., 0., 1.]);
+
+    tecs.with_world(move |world| {
+        world.set_parent(d, a).unwrap();
+        world.remove_parent(d);
+        assert_eq!((world.children(a), world.parent(d)), (vec![], None));
+    });
+}
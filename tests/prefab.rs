@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use mlua::{FromLua, IntoLua, Lua, Table, Value};
+use quadrax::cpu::simulation::tecs::{
+    TECS,
+    script::{prefab::Prefabs, registry::Registry},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Position {
+    x: f64,
+    y: f64,
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Velocity {
+    x: f64,
+    y: f64,
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Radius(f64);
+
+macro_rules! table_component {
+    ($name:ident) => {
+        impl IntoLua for $name {
+            fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+                let table = lua.create_table()?;
+                table.set("x", self.x)?;
+                table.set("y", self.y)?;
+                Ok(Value::Table(table))
+            }
+        }
+        impl FromLua for $name {
+            fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+                let table = Table::from_lua(value, lua)?;
+                Ok(Self {
+                    x: table.get("x")?,
+                    y: table.get("y")?,
+                })
+            }
+        }
+    };
+}
+table_component!(Position);
+table_component!(Velocity);
+impl IntoLua for Radius {
+    fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+        self.0.into_lua(lua)
+    }
+}
+impl FromLua for Radius {
+    fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+        Ok(Self(f64::from_lua(value, lua)?))
+    }
+}
+
+fn registry() -> Arc<Registry> {
+    Arc::new(
+        Registry::new()
+            .component::<Position>("Position")
+            .component::<Velocity>("Velocity")
+            .component::<Radius>("Radius"),
+    )
+}
+
+#[test]
+fn prefabs_inherit_and_spawn_with_overrides() {
+    let prefabs = Arc::new(Prefabs::load("tests/scripts/prefabs.luau", registry()).unwrap());
+    let mut names = prefabs.names().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["ball", "body", "boulder"]);
+
+    let tecs = TECS::new();
+    let boulders = tecs
+        .spawn_prefabs(prefabs.clone(), "boulder", 10, |i| {
+            (Position {
+                x: i as f64,
+                y: 50.0,
+            },)
+        })
+        .unwrap();
+    assert_eq!(boulders.len(), 10);
+    for (i, &boulder) in boulders.iter().enumerate() {
+        assert_eq!(
+            tecs.fetch::<(Position, Velocity, Radius)>(boulder),
+            Some((
+                Position {
+                    x: i as f64,
+                    y: 50.0
+                },
+                Velocity { x: 1.0, y: 0.0 },
+                Radius(2.0),
+            ))
+        );
+    }
+
+    // Inherited fields are kept and bases are left as they were.
+    let [boulder, ball, body] = ["boulder", "ball", "body"].map(|prefab| {
+        tecs.spawn_prefabs(prefabs.clone(), prefab, 1, |_| ())
+            .unwrap()[0]
+    });
+    assert_eq!(
+        tecs.fetch::<(Position, Radius)>(boulder),
+        Some((Position { x: 0.0, y: 100.0 }, Radius(2.0)))
+    );
+    assert_eq!(
+        tecs.fetch::<(Position, Velocity, Radius)>(ball),
+        Some((
+            Position { x: 0.0, y: 0.0 },
+            Velocity { x: 1.0, y: 0.0 },
+            Radius(0.5)
+        ))
+    );
+    assert_eq!(tecs.fetch::<(Radius,)>(body), None);
+
+    let error = tecs
+        .spawn_prefabs(prefabs, "pebble", 3, |_| ())
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Prefab pebble does not exist.");
+    tecs.shutdown().unwrap();
+}
+
+#[test]
+fn invalid_prefabs_are_reported_on_load() {
+    let error = |source: &str| {
+        Prefabs::new("scenario", source, registry())
+            .err()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        error("return { ball = { extends = 'sphere' } }"),
+        "Prefab ball extends unknown prefab sphere."
+    );
+    assert_eq!(
+        error("return { a = { extends = 'b' }, b = { extends = 'a' } }"),
+        "Prefab a extends itself through b -> a."
+    );
+    assert_eq!(
+        error("return { a = { extends = 'a' } }"),
+        "Prefab a extends itself through a."
+    );
+    let unregistered = error("return { ball = { Mass = 1 } }");
+    assert!(unregistered.starts_with("Prefab ball has an invalid Mass"));
+    assert!(unregistered.contains("Component Mass is not registered."));
+    assert!(
+        error("return { ball = { Radius = 'big' } }")
+            .starts_with("Prefab ball has an invalid Radius")
+    );
+    assert!(
+        error("return 'ball'").starts_with("Prefabs scenario must be a table of prefab tables")
+    );
+    assert!(Prefabs::load("tests/scripts/missing.luau", registry()).is_err());
+}
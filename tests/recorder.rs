@@ -0,0 +1,188 @@
+use std::{ops::Bound, sync::Arc};
+
+use bytemuck::{Pod, Zeroable};
+use quadrax::{
+    cpu::{
+        simulation::tecs::TECS,
+        storage::{codec::PodSliceCodec, recorder::Recorder},
+    },
+    gpu::{
+        backend::Backend,
+        buffer::{Buffer, BufferRole},
+    },
+};
+use tempfile::TempDir;
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug, Pod, Zeroable)]
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+}
+struct Marker;
+
+fn step(particle: &mut Particle, dt: f32) {
+    particle.velocity[1] -= 9.81 * dt;
+    particle.position[0] += particle.velocity[0] * dt;
+    particle.position[1] += particle.velocity[1] * dt;
+}
+
+#[tokio::test]
+async fn record_and_replay_trajectory() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("recording.redb");
+    let recorder: Recorder<Particle> = Recorder::new(db_path.to_str().unwrap(), 2, Some(20));
+
+    let mut particles = vec![
+        Particle {
+            position: [0.0, 0.0],
+            velocity: [1.0, 5.0],
+        },
+        Particle {
+            position: [3.0, 1.0],
+            velocity: [-2.0, 0.0],
+        },
+    ];
+    let mut trajectory = Vec::new();
+    for tick in 0..30 {
+        particles.iter_mut().for_each(|p| step(p, 0.01));
+        trajectory.push(particles.clone());
+        recorder.record(
+            tick,
+            particles
+                .iter()
+                .copied()
+                .enumerate()
+                .map(|(i, p)| (i as u64, p)),
+        );
+    }
+
+    // Only even ticks are kept, and retention drops anything 20 or more ticks behind tick 28.
+    assert!(recorder.tick(3).await.unwrap().is_empty());
+    assert!(recorder.tick(8).await.unwrap().is_empty());
+    let frame = recorder.tick(10).await.unwrap();
+    assert_eq!(frame, vec![(0, trajectory[10][0]), (1, trajectory[10][1])]);
+
+    let replayed = recorder.channel(1, 12..=20).await.unwrap();
+    let expected = (12..=20)
+        .step_by(2)
+        .map(|t| (t, trajectory[t as usize][1]))
+        .collect::<Vec<_>>();
+    assert_eq!(replayed, expected);
+
+    let frames = recorder.frames(..).await.unwrap().collect::<Vec<_>>();
+    assert_eq!(frames.first().unwrap().tick, 10);
+    assert_eq!(frames.last().unwrap().tick, 28);
+    for frame in frames {
+        let expected = &trajectory[frame.tick as usize];
+        for (channel, particle) in frame.values {
+            assert_eq!(particle, expected[channel as usize]);
+        }
+    }
+    recorder.close().await;
+}
+
+#[tokio::test]
+async fn channels_skip_ticks_they_were_not_recorded_on() {
+    let recorder: Recorder<u32> = Recorder::in_memory(1, None);
+    for tick in 0..10 {
+        let channels = if tick % 3 == 0 {
+            vec![0, 2]
+        } else {
+            vec![1, 2, 3]
+        };
+        recorder.record(
+            tick,
+            channels.into_iter().map(|c| (c, (tick * 10 + c) as u32)),
+        );
+    }
+    assert_eq!(
+        recorder.channel(1, 2..8).await.unwrap(),
+        vec![(2, 21), (4, 41), (5, 51), (7, 71)]
+    );
+    assert_eq!(
+        recorder.channel(0, ..).await.unwrap(),
+        vec![(0, 0), (3, 30), (6, 60), (9, 90)]
+    );
+    assert_eq!(
+        recorder
+            .channel(3, (Bound::Excluded(7), Bound::Unbounded))
+            .await
+            .unwrap(),
+        vec![(8, 83)]
+    );
+    assert!(recorder.channel(4, ..).await.unwrap().is_empty());
+    recorder.close().await;
+}
+
+#[tokio::test]
+async fn record_components_of_every_entity() {
+    let recorder = Arc::new(Recorder::<Particle>::in_memory(2, None));
+    let tecs = TECS::new();
+    let moving = Particle {
+        position: [0.0, 0.0],
+        velocity: [1.0, 0.0],
+    };
+    let (first, second) = tecs.with_world(move |world| {
+        let first = world.spawn((moving,));
+        let second = world.spawn((moving,));
+        world.spawn((Marker,));
+        (first, second)
+    });
+    for tick in 0..4 {
+        let recorder = recorder.clone();
+        tecs.with_world(move |world| {
+            for particle in world.query::<&mut Particle>().iter() {
+                step(particle, 0.5);
+            }
+            recorder.record_components(tick, world);
+        });
+    }
+
+    let frames = recorder.frames(..).await.unwrap().collect::<Vec<_>>();
+    assert_eq!(
+        frames.iter().map(|f| f.tick).collect::<Vec<_>>(),
+        vec![0, 2]
+    );
+    let mut expected = moving;
+    step(&mut expected, 0.5);
+    let mut channels = [first.to_bits().get(), second.to_bits().get()];
+    channels.sort();
+    assert_eq!(
+        frames[0].values,
+        channels.iter().map(|&c| (c, expected)).collect::<Vec<_>>()
+    );
+    step(&mut expected, 0.5);
+    step(&mut expected, 0.5);
+    assert_eq!(
+        recorder
+            .channel(second.to_bits().get(), 2..=2)
+            .await
+            .unwrap(),
+        vec![(2, expected)]
+    );
+    drop(tecs);
+    Arc::into_inner(recorder).unwrap().close().await;
+}
+
+#[tokio::test]
+async fn record_buffer_contents() {
+    let backend = Backend::new().await;
+    let buffer = Buffer::new(
+        backend.arc_mutex().clone(),
+        vec![1u32, 2, 3],
+        BufferRole::Generic,
+    )
+    .await;
+    let recorder = Recorder::<Vec<u32>, PodSliceCodec>::in_memory(2, None);
+    recorder.record_buffer(0, 7, &buffer).await;
+    buffer.write(vec![4u32, 5, 6]).await;
+    recorder.record_buffer(1, 7, &buffer).await;
+    recorder.record_buffer(2, 7, &buffer).await;
+
+    assert_eq!(
+        recorder.channel(7, ..).await.unwrap(),
+        vec![(0, vec![1, 2, 3]), (2, vec![4, 5, 6])]
+    );
+    recorder.close().await;
+}
@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use quadrax::{
+    cpu::simulation::tecs::{
+        TECS,
+        script::{ScriptSystem, registry::Registry},
+    },
+    reload::Reloader,
+};
+use tempfile::TempDir;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Counter(f64);
+impl mlua::IntoLua for Counter {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        self.0.into_lua(lua)
+    }
+}
+impl mlua::FromLua for Counter {
+    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+        Ok(Self(f64::from_lua(value, lua)?))
+    }
+}
+
+/// Adds `step` to every counter each tick.
+fn counting_script(step: u32) -> String {
+    format!(
+        r#"
+        return function(world)
+            for entity, count in world:query("Counter") do
+                world:set(entity, "Counter", count + {step})
+            end
+        end
+        "#
+    )
+}
+
+/// Rewrites a watched file, moving its modification time forward so that the change shows
+/// whatever the timestamp resolution.
+fn rewrite(path: &Path, contents: &str, age: u64) {
+    write_at(
+        path,
+        contents.as_bytes(),
+        SystemTime::now() + Duration::from_secs(age),
+    );
+}
+
+fn write_at(path: &Path, contents: &[u8], modified: SystemTime) {
+    fs::write(path, contents).unwrap();
+    let file = fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(modified).unwrap();
+}
+
+#[tokio::test]
+async fn script_reloads_keep_simulation_state() {
+    let tmp_dir = TempDir::new().unwrap();
+    let path = tmp_dir.path().join("counting.luau");
+    let path_str = path.to_str().unwrap();
+    fs::write(&path, counting_script(1)).unwrap();
+
+    let registry = Arc::new(Registry::new().component::<Counter>("Counter"));
+    let script = ScriptSystem::load(path_str, registry).unwrap();
+    let mut reloader = Reloader::new();
+    reloader.watch_script(path_str, script.reloader());
+
+    let tecs = TECS::new();
+    let entity = tecs.create_entity((Counter(0.0),));
+    tecs.add_systems(vec![script]).unwrap();
+    tecs.tick_n(3).await.unwrap();
+    assert!(reloader.poll(&tecs).await.is_empty());
+
+    rewrite(&path, &counting_script(10), 1);
+    let reloaded = reloader.poll(&tecs).await;
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded[0].path, path_str);
+    assert!(reloaded[0].result.is_ok());
+    tecs.tick_n(2).await.unwrap();
+    assert_eq!(tecs.fetch::<(Counter,)>(entity), Some((Counter(23.0),)));
+
+    // A broken version is reported once and the previous one keeps running.
+    rewrite(&path, "return function(world", 2);
+    let reloaded = reloader.poll(&tecs).await;
+    assert_eq!(reloaded.len(), 1);
+    assert!(reloaded[0].result.is_err());
+    assert!(reloader.poll(&tecs).await.is_empty());
+    tecs.tick().await.unwrap();
+    assert_eq!(tecs.fetch::<(Counter,)>(entity), Some((Counter(33.0),)));
+
+    // A file that is briefly missing is picked up once it is back.
+    fs::remove_file(&path).unwrap();
+    assert!(reloader.poll(&tecs).await.is_empty());
+    rewrite(&path, &counting_script(100), 3);
+    assert!(reloader.poll(&tecs).await[0].result.is_ok());
+    tecs.tick().await.unwrap();
+    assert_eq!(tecs.fetch::<(Counter,)>(entity), Some((Counter(133.0),)));
+}
+
+#[tokio::test]
+async fn unreadable_files_are_retried() {
+    let tmp_dir = TempDir::new().unwrap();
+    let path = tmp_dir.path().join("counting.luau");
+    let path_str = path.to_str().unwrap();
+    fs::write(&path, counting_script(1)).unwrap();
+
+    let registry = Arc::new(Registry::new().component::<Counter>("Counter"));
+    let script = ScriptSystem::load(path_str, registry).unwrap();
+    let mut reloader = Reloader::new();
+    reloader.watch_script(path_str, script.reloader());
+    let tecs = TECS::new();
+    let entity = tecs.create_entity((Counter(0.0),));
+    tecs.add_systems(vec![script]).unwrap();
+
+    // Caught half-written, as invalid UTF-8, then finished with the same length and time.
+    let source = counting_script(5);
+    let mut garbled = source.clone().into_bytes();
+    garbled[0] = 0xff;
+    let modified = SystemTime::now() + Duration::from_secs(1);
+    write_at(&path, &garbled, modified);
+    assert!(reloader.poll(&tecs).await.is_empty());
+    write_at(&path, source.as_bytes(), modified);
+    let reloaded = reloader.poll(&tecs).await;
+    assert_eq!(reloaded.len(), 1);
+    assert!(reloaded[0].result.is_ok());
+    tecs.tick().await.unwrap();
+    assert_eq!(tecs.fetch::<(Counter,)>(entity), Some((Counter(5.0),)));
+}
+
+#[tokio::test]
+async fn replacements_wait_to_be_applied() {
+    let registry = Arc::new(Registry::new().component::<Counter>("Counter"));
+    let script = ScriptSystem::new("counting", &counting_script(1), registry).unwrap();
+    let reloader = script.reloader();
+    let tecs = TECS::new();
+    let entity = tecs.create_entity((Counter(0.0),));
+    tecs.add_systems(vec![script]).unwrap();
+
+    let replacement = reloader.reload(&counting_script(10)).unwrap();
+    tecs.tick().await.unwrap();
+    assert_eq!(tecs.fetch::<(Counter,)>(entity), Some((Counter(1.0),)));
+    replacement.apply();
+    tecs.tick().await.unwrap();
+    assert_eq!(tecs.fetch::<(Counter,)>(entity), Some((Counter(11.0),)));
+}
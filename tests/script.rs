@@ -0,0 +1,287 @@
+use std::{sync::Arc, time::Duration};
+
+use mlua::{FromLua, IntoLua, Lua, Table, Value};
+use quadrax::cpu::simulation::tecs::{
+    EntityID, TECS, World,
+    fault::SystemPanic,
+    script::{ScriptSystem, registry::Registry, sandbox::Limits},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Position {
+    x: f64,
+    y: f64,
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Velocity {
+    x: f64,
+    y: f64,
+}
+#[derive(Clone, Copy)]
+struct Gravity(f64);
+
+macro_rules! table_component {
+    ($name:ident) => {
+        impl IntoLua for $name {
+            fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+                let table = lua.create_table()?;
+                table.set("x", self.x)?;
+                table.set("y", self.y)?;
+                Ok(Value::Table(table))
+            }
+        }
+        impl FromLua for $name {
+            fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+                let table = Table::from_lua(value, lua)?;
+                Ok(Self {
+                    x: table.get("x")?,
+                    y: table.get("y")?,
+                })
+            }
+        }
+    };
+}
+table_component!(Position);
+table_component!(Velocity);
+impl IntoLua for Gravity {
+    fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+        self.0.into_lua(lua)
+    }
+}
+
+fn registry() -> Arc<Registry> {
+    Arc::new(
+        Registry::new()
+            .component::<Position>("Position")
+            .component::<Velocity>("Velocity")
+            .resource::<Gravity>("Gravity"),
+    )
+}
+
+/// Starts a simulation with bodies dropped from a range of heights.
+fn drop_bodies() -> TECS {
+    let tecs = TECS::new();
+    tecs.insert_resource(Gravity(9.81));
+    for i in 1..=5 {
+        tecs.create_entity((
+            Position {
+                x: 0.0,
+                y: 2.0 * i as f64,
+            },
+            Velocity {
+                x: i as f64,
+                y: 0.0,
+            },
+        ));
+    }
+    tecs
+}
+
+fn bodies(tecs: &TECS) -> Vec<(Position, Velocity)> {
+    let mut bodies = tecs.with_world(|world| {
+        world
+            .query::<(&Position, &Velocity)>()
+            .iter()
+            .map(|(p, v)| (*p, *v))
+            .collect::<Vec<_>>()
+    });
+    bodies.sort_by(|a, b| a.0.x.total_cmp(&b.0.x).then(a.0.y.total_cmp(&b.0.y)));
+    bodies
+}
+
+const SPAWNER: &str = r#"
+return function(world)
+    if world.tick < 3 then
+        world:spawn({
+            Position = { x = -world.tick, y = 10 },
+            Velocity = { x = 0, y = 1 },
+        })
+    end
+end
+"#;
+
+#[test]
+fn lua_gravity_matches_rust() {
+    let scripted = drop_bodies();
+    scripted
+        .add_systems(vec![
+            ScriptSystem::new("spawner", SPAWNER, registry()).unwrap(),
+            ScriptSystem::load("tests/scripts/gravity.luau", registry()).unwrap(),
+        ])
+        .unwrap();
+
+    let reference = drop_bodies();
+    reference
+        .add_systems(vec![
+            |world: &mut World| {
+                let tick = world.time().tick();
+                if tick < 3 {
+                    world.spawn((
+                        Position {
+                            x: -(tick as f64),
+                            y: 10.0,
+                        },
+                        Velocity { x: 0.0, y: 1.0 },
+                    ));
+                }
+            },
+            |world: &mut World| {
+                let g = world.resource::<Gravity>().unwrap().0;
+                let dt = world.time().dt();
+                let mut fallen = Vec::new();
+                for (entity, position, velocity) in
+                    world.query_mut::<(EntityID, &mut Position, &mut Velocity)>()
+                {
+                    velocity.y -= g * dt;
+                    position.x += velocity.x * dt;
+                    position.y += velocity.y * dt;
+                    if position.y < 0.0 {
+                        fallen.push(entity);
+                    }
+                }
+                for entity in fallen {
+                    world.despawn(entity).unwrap();
+                }
+            },
+        ])
+        .unwrap();
+
+    for ticks in [1, 10, 49] {
+        scripted.tick_n(ticks).wait().unwrap();
+        reference.tick_n(ticks).wait().unwrap();
+        let (scripted, reference) = (bodies(&scripted), bodies(&reference));
+        assert_eq!(scripted.len(), reference.len());
+        for ((p, v), (rp, rv)) in scripted.iter().zip(&reference) {
+            for (a, b) in [(p.x, rp.x), (p.y, rp.y), (v.x, rv.x), (v.y, rv.y)] {
+                assert!((a - b).abs() < 1e-9, "{a} != {b}");
+            }
+        }
+    }
+    // After a second the two lowest bodies have hit the floor; the three spawned ones remain.
+    assert_eq!(bodies(&scripted).len(), 6);
+    scripted.shutdown().unwrap();
+    reference.shutdown().unwrap();
+}
+
+#[test]
+fn script_errors_are_reported() {
+    let error = ScriptSystem::new("string", "return 'gravity'", registry())
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "Script string must return its system function, not a string."
+    );
+    assert!(ScriptSystem::new("syntax", "return function(", registry()).is_err());
+    assert!(ScriptSystem::load("tests/scripts/missing.luau", registry()).is_err());
+
+    let tecs = TECS::new();
+    let entity = tecs.create_entity((Position { x: 0.0, y: 0.0 },));
+    let source = r#"
+        return function(world)
+            for entity in world:query("Position") do
+                world:get(entity, "Mass")
+            end
+        end
+    "#;
+    tecs.add_systems(vec![
+        ScriptSystem::new("weigh", source, registry()).unwrap(),
+    ])
+    .unwrap();
+    let error = tecs.tick().wait().unwrap_err();
+    let fault = error.downcast_ref::<SystemPanic>().unwrap();
+    assert_eq!((fault.system.as_str(), fault.tick), ("weigh", 0));
+    assert!(fault.message.contains("Component Mass is not registered."));
+    assert!(tecs.contains(entity));
+}
+
+#[test]
+fn sandboxed_scripts_are_disabled_at_their_limits() {
+    let limits = Limits {
+        interrupts: Some(100_000),
+        time: Some(Duration::from_millis(200)),
+        memory: Some(8 << 20),
+    };
+    let runaway = "return function(world) while true do end end";
+    let hog = r#"
+        return function(world)
+            local hoard = {}
+            for i = 1, 1e9 do
+                hoard[i] = string.rep("x", 1024) .. i
+            end
+        end
+    "#;
+    let sandboxed =
+        |name, source, limits| ScriptSystem::sandboxed(name, source, registry(), limits).unwrap();
+
+    let tecs = TECS::new();
+    let entity = tecs.create_entity((Position { x: 0.0, y: 0.0 },));
+    tecs.add_systems(vec![
+        sandboxed("runaway", runaway, limits),
+        sandboxed(
+            "slow",
+            runaway,
+            Limits {
+                interrupts: None,
+                ..limits
+            },
+        ),
+        sandboxed("hog", hog, limits),
+        ScriptSystem::sandboxed(
+            "mover",
+            r#"
+            return function(world)
+                for entity, position in world:query("Position") do
+                    position.x += 1
+                    world:set(entity, "Position", position)
+                end
+            end
+            "#,
+            registry(),
+            limits,
+        )
+        .unwrap(),
+    ])
+    .unwrap();
+
+    let error = tecs.tick().wait().unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<SystemPanic>().unwrap().system,
+        "runaway"
+    );
+    let faults = tecs.faults();
+    let messages = faults
+        .iter()
+        .map(|fault| (fault.system.as_str(), fault.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(messages.len(), 3);
+    assert!(messages[0].1.contains("budget of 100000 interrupts"));
+    assert_eq!(messages[1].0, "slow");
+    assert!(messages[1].1.contains("time budget of 200ms"));
+    assert_eq!(messages[2].0, "hog");
+    assert!(messages[2].1.contains("memory"));
+
+    // The offenders are disabled; the well-behaved script carries on.
+    assert_eq!(tecs.tick_n(2).wait().unwrap(), 3);
+    assert_eq!(tecs.fetch::<(Position,)>(entity).unwrap().0.x, 3.0);
+    assert_eq!(tecs.faults().len(), 3);
+    tecs.shutdown().unwrap();
+
+    // Sandboxed scripts have no os, debug or require, and loading them is budgeted too.
+    for source in [
+        "return os.time()",
+        "return debug.traceback()",
+        "return require('gravity')",
+        "while true do end",
+    ] {
+        assert!(ScriptSystem::sandboxed("restricted", source, registry(), limits).is_err());
+    }
+    assert!(
+        ScriptSystem::new(
+            "trusted",
+            "local _ = os.time() return function() end",
+            registry()
+        )
+        .is_ok()
+    );
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use mlua::{FromLuaMulti, Lua};
+use quadrax::cpu::{
+    maths::{matrix::Matrix, vector::Vector},
+    simulation::tecs::{
+        TECS,
+        script::{ScriptSystem, maths, registry::Registry},
+    },
+};
+
+fn lua() -> Lua {
+    let lua = Lua::new();
+    maths::register(&lua).unwrap();
+    lua
+}
+
+fn eval<T: FromLuaMulti>(lua: &Lua, expression: &str) -> T {
+    lua.load(format!("return {expression}")).eval().unwrap()
+}
+
+#[test]
+fn lua_vectors_match_rust() {
+    let lua = lua();
+    lua.load("a, b = vec3(1, 2, 3), vec3(4, -5, 6)")
+        .exec()
+        .unwrap();
+    let (a, b) = (Vector::new([1., 2., 3.]), Vector::new([4., -5., 6.]));
+    assert_eq!(eval::<String>(&lua, "type(a)"), "vector");
+    assert_eq!(eval::<Vector<3>>(&lua, "a + b"), a + b);
+    assert_eq!(
+        eval::<Vector<3>>(&lua, "a * b - b / 2"),
+        a * b - b / Vector::new([2.; 3])
+    );
+    assert_eq!(eval::<f32>(&lua, "a:dot(b)"), a.dot(&b));
+    assert_eq!(eval::<Vector<3>>(&lua, "a:cross(b)"), a.cross(&b));
+
+    lua.load("c, d = vec4(1, 2, 3, 4), vec4(-1, 0.5, 2, 8)")
+        .exec()
+        .unwrap();
+    let (c, d) = (
+        Vector::new([1., 2., 3., 4.]),
+        Vector::new([-1., 0.5, 2., 8.]),
+    );
+    assert_eq!(eval::<Vector<4>>(&lua, "c + d"), c + d);
+    assert_eq!(eval::<Vector<4>>(&lua, "c - d"), c - d);
+    assert_eq!(
+        eval::<Vector<4>>(&lua, "2 * c / d"),
+        Vector::new([2.; 4]) * c / d
+    );
+    assert_eq!(eval::<Vector<4>>(&lua, "-c"), Vector::new([0.; 4]) - c);
+    assert_eq!(eval::<f32>(&lua, "c:dot(d)"), c.dot(&d));
+    assert_eq!(
+        eval::<(f32, f32)>(&lua, "c:sum(), c:prod()"),
+        (c.sum(), c.prod())
+    );
+    assert!(eval::<bool>(&lua, "c == vec4(1, 2, 3, 4) and c ~= d"));
+    assert_eq!(
+        eval::<String>(&lua, "tostring(vec2(1, 2))"),
+        "vec2[1.0, 2.0]"
+    );
+
+    lua.load("e = vec2(1, 2); e.y = e.x + 5").exec().unwrap();
+    assert_eq!(eval::<Vector<2>>(&lua, "e"), Vector::new([1., 6.]));
+    assert!(lua.load("return e.z").eval::<f32>().is_err());
+    assert!(lua.load("return c + e").eval::<Vector<4>>().is_err());
+}
+
+#[test]
+fn lua_matrices_match_rust() {
+    let lua = lua();
+    lua.load(
+        r#"
+        a = mat({ { 1, 2, 3 }, { 4, 5, 6 } })
+        b = mat({ { 7, 8 }, { 9, 10 }, { 11, 12 } })
+        "#,
+    )
+    .exec()
+    .unwrap();
+    let a = Matrix::<3, 2>::new([[1., 2., 3.], [4., 5., 6.]]);
+    let b = Matrix::<2, 3>::new([[7., 8.], [9., 10.], [11., 12.]]);
+
+    assert_eq!(eval::<(usize, usize)>(&lua, "a.rows, a.cols"), (2, 3));
+    assert_eq!(eval::<Matrix<2, 2>>(&lua, "a:matmul(b)"), a | b);
+    assert_eq!(eval::<Matrix<3, 3>>(&lua, "b:matmul(a)"), b | a);
+    assert_eq!(eval::<Matrix<2, 3>>(&lua, "a:transpose()"), a.transpose());
+    assert_eq!(
+        eval::<Matrix<3, 2>>(&lua, "a + a * a - 1"),
+        a + a * a - Matrix::new([[1.; 3]; 2])
+    );
+    assert_eq!(
+        eval::<Matrix<3, 2>>(&lua, "-a / 2"),
+        (Matrix::new([[0.; 3]; 2]) - a) / Matrix::new([[2.; 3]; 2])
+    );
+    assert_eq!(eval::<f32>(&lua, "a:dot(a)"), a.dot(&a));
+    assert_eq!(
+        eval::<(f32, f32)>(&lua, "a:sum(), a:prod()"),
+        (a.sum(), a.prod())
+    );
+    assert_eq!(eval::<f32>(&lua, "a:get(2, 3)"), 6.);
+    assert_eq!(
+        eval::<Vector<3>>(&lua, "a:row(1)"),
+        Vector::new([1., 2., 3.])
+    );
+    assert_eq!(
+        eval::<Vector<2>>(&lua, "a:matmul(vec3(1, 0, -1))"),
+        Vector::new([-2., -2.])
+    );
+    assert!(eval::<bool>(&lua, "a == mat({ { 1, 2, 3 }, { 4, 5, 6 } })"));
+
+    for rhs in ["a", "mat({ { 1, 2 }, { 3, 4 } })"] {
+        let error = lua
+            .load(format!("return a:matmul({rhs})"))
+            .exec()
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Cannot multiply a 2 by 3 matrix by a matrix without 3 rows."),
+            "{error}"
+        );
+    }
+    assert!(lua.load("return a:get(3, 1)").exec().is_err());
+    assert!(lua.load("return mat({ { 1, 2 }, { 3 } })").exec().is_err());
+    assert!(
+        lua.load("return mat({ { 1, 2, 3, 4, 5 } })")
+            .exec()
+            .is_err()
+    );
+}
+
+#[test]
+fn scripts_transform_vector_components() {
+    let registry = Arc::new(
+        Registry::new()
+            .component::<Vector<3>>("Heading")
+            .resource::<Matrix<3, 3>>("Turn"),
+    );
+    let source = r#"
+        return function(world)
+            local turn = world:resource("Turn")
+            for entity, heading in world:query("Heading") do
+                world:set(entity, "Heading", turn:matmul(heading))
+            end
+        end
+    "#;
+    let turn = Matrix::new([[0., -1., 0.], [1., 0., 0.], [0., 0., 1.]]);
+    let tecs = TECS::new();
+    tecs.insert_resource(turn);
+    let entity = tecs.create_entity((Vector::new([1., 2., 3.]),));
+    tecs.add_systems(vec![
+        ScriptSystem::sandboxed("turn", source, registry, Default::default()).unwrap(),
+    ])
+    .unwrap();
+    tecs.tick().wait().unwrap();
+    let (heading,) = tecs.fetch::<(Vector<3>,)>(entity).unwrap();
+    assert_eq!(heading, Vector::new([-2., 1., 3.]));
+    tecs.shutdown().unwrap();
+}
@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use quadrax::cpu::storage::Storage;
 use tempfile::TempDir;
 
@@ -27,3 +29,532 @@ async fn storage_worker() {
 
     storage.close().await;
 }
+
+#[tokio::test]
+async fn storage_codecs() {
+    use quadrax::cpu::storage::codec::{JsonCodec, PodCodec, PodSliceCodec};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        species: Vec<u32>,
+    }
+
+    let tmp_dir = TempDir::new().unwrap();
+    let configs_path = tmp_dir.path().join("configs.redb");
+    let positions_path = tmp_dir.path().join("positions.redb");
+
+    let configs: Storage<String, Config, JsonCodec, JsonCodec> =
+        Storage::new(configs_path.to_str().unwrap());
+    let config = Config {
+        name: "run-a".into(),
+        species: vec![1, 2, 3],
+    };
+    configs.insert("run-a".into(), config.clone()).await;
+    assert_eq!(configs.get("run-a".into()).await, Some(config));
+    assert_eq!(configs.get("run-b".into()).await, None);
+    configs.close().await;
+
+    let positions: Storage<u32, Vec<[f32; 3]>, PodCodec, PodSliceCodec> =
+        Storage::new(positions_path.to_str().unwrap());
+    let data = vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]];
+    positions.insert(7, data.clone()).await;
+    assert_eq!(positions.get(7).await, Some(data));
+    let sum = positions
+        .view_slice(7, |slice| slice.map(|s| s.iter().flatten().sum::<f32>()))
+        .await
+        .unwrap();
+    assert_eq!(sum, Some(21.0));
+    positions.close().await;
+}
+
+#[tokio::test]
+async fn storage_watch() {
+    use quadrax::cpu::storage::{
+        codec::{PodCodec, Utf8Codec},
+        watch::{Lagged, Overflow, StorageEvent},
+    };
+
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("test.redb");
+    let storage: Storage<String, u64, Utf8Codec, PodCodec> =
+        Storage::new(db_path.to_str().unwrap());
+
+    let mut key_watch = storage.watch("a/1".into(), 16, Overflow::Lag).await;
+    let mut prefix_watch = storage.watch_prefix("a/", 16, Overflow::Lag).await;
+    let mut lagging_watch = storage.watch_prefix("b/", 2, Overflow::Lag).await;
+    let mut dropping_watch = storage.watch_prefix("b/", 2, Overflow::DropOldest).await;
+
+    storage.insert("a/1".into(), 1).await;
+    storage.insert("a/2".into(), 2).await;
+    storage.insert("c/1".into(), 3).await;
+    storage.remove("a/1".into()).await;
+    storage.remove("a/3".into()).await;
+    for i in 0..4 {
+        storage.insert(format!("b/{i}"), i).await;
+    }
+    storage.close().await;
+
+    assert_eq!(
+        key_watch.recv().await.unwrap().unwrap(),
+        StorageEvent::Insert {
+            key: "a/1".into(),
+            value: 1
+        }
+    );
+    assert_eq!(
+        key_watch.recv().await.unwrap().unwrap(),
+        StorageEvent::Remove { key: "a/1".into() }
+    );
+    assert!(key_watch.recv().await.is_none());
+
+    let mut prefix_events = Vec::new();
+    while let Some(event) = prefix_watch.recv().await {
+        prefix_events.push(event.unwrap());
+    }
+    assert_eq!(
+        prefix_events,
+        vec![
+            StorageEvent::Insert {
+                key: "a/1".into(),
+                value: 1
+            },
+            StorageEvent::Insert {
+                key: "a/2".into(),
+                value: 2
+            },
+            StorageEvent::Remove { key: "a/1".into() },
+        ]
+    );
+
+    let lag = lagging_watch.recv().await.unwrap().unwrap_err();
+    assert_eq!(lag.downcast_ref::<Lagged>().unwrap().0, 2);
+    assert_eq!(
+        lagging_watch.recv().await.unwrap().unwrap(),
+        StorageEvent::Insert {
+            key: "b/2".into(),
+            value: 2
+        }
+    );
+
+    assert_eq!(
+        dropping_watch.recv().await.unwrap().unwrap(),
+        StorageEvent::Insert {
+            key: "b/2".into(),
+            value: 2
+        }
+    );
+
+    // Zero capacity holds one event rather than panicking.
+    let storage: Storage<String, u64, Utf8Codec, PodCodec> = Storage::in_memory();
+    let mut unbuffered_watch = storage.watch("a/1".into(), 0, Overflow::DropOldest).await;
+    storage.insert("a/1".into(), 1).await;
+    storage.insert("a/1".into(), 2).await;
+    storage.close().await;
+    assert_eq!(
+        unbuffered_watch.recv().await.unwrap().unwrap(),
+        StorageEvent::Insert {
+            key: "a/1".into(),
+            value: 2
+        }
+    );
+    assert!(unbuffered_watch.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn storage_in_memory() {
+    let storage: Storage<u32, u64> = Storage::in_memory();
+    storage.insert(1, 100).await;
+    storage.insert(2, 200).await;
+    storage.remove(1).await;
+    assert_eq!(storage.get(1).await, None);
+    assert_eq!(storage.get(2).await, Some(200));
+
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("snapshot.redb");
+    let db_path_str = db_path.to_str().unwrap();
+    storage.snapshot(db_path_str).await.unwrap();
+    storage.insert(3, 300).await;
+    storage.close().await;
+
+    let loaded: Storage<u32, u64> = Storage::load_into_memory(db_path_str).unwrap();
+    assert_eq!(loaded.get(2).await, Some(200));
+    assert_eq!(loaded.get(3).await, None);
+    loaded.insert(4, 400).await;
+    loaded.close().await;
+
+    let file: Storage<u32, u64> = Storage::new(db_path_str);
+    assert_eq!(file.get(2).await, Some(200));
+    assert_eq!(file.get(4).await, None);
+    file.close().await;
+
+    // A second snapshot to the same path replaces the first rather than adding to it.
+    let storage: Storage<u32, u64> = Storage::in_memory();
+    storage.insert(5, 500).await;
+    storage.snapshot(db_path_str).await.unwrap();
+    storage.close().await;
+    let file: Storage<u32, u64> = Storage::new(db_path_str);
+    assert_eq!(file.range(..).await.unwrap(), vec![(5, 500)]);
+    file.close().await;
+}
+
+#[tokio::test]
+async fn storage_backup_compact_stats() {
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("live.redb");
+    let backup_path = tmp_dir.path().join("backup.redb");
+    let storage: Storage<u32, u64> = Storage::new(db_path.to_str().unwrap());
+
+    for i in 0..1000 {
+        storage.insert(i, i as u64 * 10).await;
+    }
+    // The backup is queued behind the first 1000 writes and copies while the rest go in.
+    let (backup, _) = tokio::join!(storage.backup(backup_path.to_str().unwrap()), async {
+        for i in 1000..2000 {
+            storage.insert(i, i as u64 * 10).await;
+        }
+    });
+    backup.unwrap();
+
+    let stats = storage.stats().await.unwrap();
+    assert_eq!(stats.tables.len(), 1);
+    assert_eq!(stats.tables[0].name, "data");
+    assert_eq!(stats.tables[0].entries, 2000);
+    assert!(stats.tables[0].stored_bytes > 0);
+    assert!(stats.file_size.unwrap() > 0);
+
+    storage.remove_range(..).await;
+    storage.compact().await.unwrap();
+    assert_eq!(storage.stats().await.unwrap().tables[0].entries, 0);
+    storage.close().await;
+
+    let backup: Storage<u32, u64> = Storage::new(backup_path.to_str().unwrap());
+    let entries = backup.range(..).await.unwrap();
+    let mut keys = entries.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, (0..1000).collect::<Vec<_>>());
+    assert!(entries.iter().all(|(k, v)| *v == *k as u64 * 10));
+    backup.close().await;
+}
+
+#[tokio::test]
+async fn backup_runs_alongside_writes() {
+    const BACKED_UP: u32 = 50_000;
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("live.redb");
+    let backup_path = tmp_dir.path().join("backup.redb");
+    let storage: Storage<u32, [u64; 64]> = Storage::new(db_path.to_str().unwrap());
+    let keys = (0..BACKED_UP).collect::<Vec<_>>();
+    for chunk in keys.chunks(5_000) {
+        storage
+            .insert_batch(chunk.iter().map(|&k| (k, [k as u64; 64])).collect())
+            .await;
+    }
+
+    // Every write is committed and read back while the backup is still copying.
+    let finished = AtomicBool::new(false);
+    let (backup, written) = tokio::join!(
+        async {
+            let backup = storage.backup(backup_path.to_str().unwrap()).await;
+            finished.store(true, Ordering::SeqCst);
+            backup
+        },
+        async {
+            let mut written = 0;
+            while !finished.load(Ordering::SeqCst) {
+                let key = BACKED_UP + written;
+                storage.insert(key, [key as u64; 64]).await;
+                assert_eq!(storage.get(key).await, Some([key as u64; 64]));
+                written += 1;
+                tokio::task::yield_now().await;
+            }
+            written
+        }
+    );
+    backup.unwrap();
+    assert!(written > 0, "No write went in while the backup ran.");
+    assert_eq!(
+        storage.range(..).await.unwrap().len(),
+        (BACKED_UP + written) as usize
+    );
+    storage.close().await;
+
+    // The backup holds exactly the writes queued before it, and none of those made during it.
+    let backup: Storage<u32, [u64; 64]> = Storage::new(backup_path.to_str().unwrap());
+    let mut entries = backup.range(..).await.unwrap();
+    entries.sort_by_key(|(k, _)| *k);
+    assert_eq!(
+        entries.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+        (0..BACKED_UP).collect::<Vec<_>>()
+    );
+    assert!(entries.iter().all(|(k, v)| *v == [*k as u64; 64]));
+    backup.close().await;
+}
+
+#[tokio::test]
+async fn repeated_backups_replace_each_other() {
+    let tmp_dir = TempDir::new().unwrap();
+    let backup_path = tmp_dir.path().join("backup.redb");
+    let backup_path = backup_path.to_str().unwrap();
+    let storage: Storage<u32, u64> = Storage::in_memory();
+    storage
+        .insert_batch((0..100).map(|i| (i, i as u64)).collect())
+        .await;
+    storage.backup(backup_path).await.unwrap();
+
+    storage.remove_range(50..).await;
+    storage.insert(7, 70).await;
+    storage.backup(backup_path).await.unwrap();
+    let mut expected = storage.range(..).await.unwrap();
+    storage.close().await;
+
+    let backup: Storage<u32, u64> = Storage::new(backup_path);
+    let mut entries = backup.range(..).await.unwrap();
+    entries.sort();
+    expected.sort();
+    assert_eq!(entries.len(), 50);
+    assert_eq!(entries, expected);
+    backup.close().await;
+}
+
+#[tokio::test]
+async fn storage_secondary_index() {
+    use bytemuck::{Pod, Zeroable};
+    use quadrax::cpu::storage::codec::OrderedCodec;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, PartialEq, Debug, Pod, Zeroable)]
+    struct Particle {
+        species: u32,
+        mass: f32,
+    }
+    let particle = |species, mass| Particle { species, mass };
+
+    let storage: Storage<u32, Particle, OrderedCodec> = Storage::in_memory();
+    storage.insert(1, particle(3, 1.0)).await;
+    storage.insert(2, particle(3, 2.0)).await;
+
+    // Registering after the fact indexes what is already stored.
+    let species = storage
+        .add_index::<u32, OrderedCodec>("species", |p| p.species)
+        .await
+        .unwrap();
+    storage.insert(3, particle(7, 3.0)).await;
+    storage.insert(4, particle(256, 4.0)).await;
+    assert_eq!(
+        storage.get_by_index(&species, 3).await.unwrap(),
+        vec![(1, particle(3, 1.0)), (2, particle(3, 2.0))]
+    );
+
+    // Updates move a record between index keys, removes drop it.
+    storage.insert(2, particle(7, 2.5)).await;
+    storage.remove(3).await;
+    assert_eq!(
+        storage.get_by_index(&species, 3).await.unwrap(),
+        vec![(1, particle(3, 1.0))]
+    );
+    assert_eq!(
+        storage.get_by_index(&species, 7).await.unwrap(),
+        vec![(2, particle(7, 2.5))]
+    );
+    assert_eq!(
+        storage.range_by_index(&species, 4..).await.unwrap(),
+        vec![(2, particle(7, 2.5)), (4, particle(256, 4.0))]
+    );
+    assert_eq!(
+        storage.range_by_index(&species, ..=7).await.unwrap(),
+        vec![(1, particle(3, 1.0)), (2, particle(7, 2.5))]
+    );
+
+    storage.remove_range(..).await;
+    assert!(
+        storage
+            .range_by_index(&species, ..)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    storage.close().await;
+}
+
+#[tokio::test]
+async fn indexes_must_be_added_again_after_reopening() {
+    use quadrax::cpu::storage::codec::OrderedCodec;
+
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("indexed.redb");
+    let db_path = db_path.to_str().unwrap();
+    let storage: Storage<u32, u64, OrderedCodec> = Storage::new(db_path);
+    storage
+        .add_index::<u64, OrderedCodec>("parity", |v| v % 2)
+        .await
+        .unwrap();
+    storage.insert(1, 11).await;
+    storage.close().await;
+
+    // Without its extractor the index would miss these writes, so they are refused.
+    let storage: Storage<u32, u64, OrderedCodec> = Storage::new(db_path);
+    let error = storage.try_insert(2, 12).await.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Index parity must be added again before writing to this storage."
+    );
+    assert!(storage.try_remove(1).await.is_err());
+    assert_eq!(storage.get(1).await, Some(11));
+    assert_eq!(storage.get(2).await, None);
+
+    let parity = storage
+        .add_index::<u64, OrderedCodec>("parity", |v| v % 2)
+        .await
+        .unwrap();
+    storage.try_insert(2, 12).await.unwrap();
+    storage.insert(3, 13).await;
+    assert_eq!(
+        storage.get_by_index(&parity, 1).await.unwrap(),
+        vec![(1, 11), (3, 13)]
+    );
+    assert_eq!(
+        storage.get_by_index(&parity, 0).await.unwrap(),
+        vec![(2, 12)]
+    );
+    storage.close().await;
+}
+
+#[tokio::test]
+async fn storage_schema_migration() {
+    use bytemuck::{Pod, Zeroable};
+    use quadrax::cpu::storage::{
+        codec::PodCodec,
+        schema::{Schema, SchemaMismatch},
+    };
+
+    #[repr(C)]
+    #[derive(Pod, Zeroable, Clone, Copy, PartialEq, Debug)]
+    struct BodyV1 {
+        position: [f32; 3],
+    }
+    #[repr(C)]
+    #[derive(Pod, Zeroable, Clone, Copy, PartialEq, Debug)]
+    struct BodyV2 {
+        position: [f32; 3],
+        mass: f32,
+    }
+
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("bodies.redb");
+    let path = db_path.to_str().unwrap();
+    let v1 = Schema::of::<u32, BodyV1>(1);
+    let v2 = Schema::named::<u32, BodyV2>(2);
+
+    let storage: Storage<u32, BodyV1> = Storage::open(path, v1.clone()).unwrap();
+    for i in 0..10 {
+        let position = [i as f32; 3];
+        storage.insert(i, BodyV1 { position }).await;
+    }
+    storage.close().await;
+    assert_eq!(Schema::read(path).unwrap(), Some(v1.clone()));
+
+    let error = Storage::<u32, BodyV2>::open(path, v2.clone())
+        .err()
+        .unwrap();
+    let mismatch = error.downcast_ref::<SchemaMismatch>().unwrap();
+    assert_eq!((&mismatch.stored, &mismatch.requested), (&v1, &v2));
+    assert!(
+        Storage::<u32, BodyV2>::migrate::<BodyV1, PodCodec>(path, &v1, &v1, |_| unreachable!())
+            .is_err()
+    );
+
+    let failed = Storage::<u32, BodyV2>::migrate::<BodyV1, PodCodec>(path, &v1, &v2, |body| {
+        anyhow::ensure!(body.position[0] < 5.0, "too far");
+        Ok(BodyV2 {
+            position: body.position,
+            mass: 1.0,
+        })
+    });
+    assert!(failed.is_err());
+    assert_eq!(Schema::read(path).unwrap(), Some(v1.clone()));
+
+    Storage::<u32, BodyV2>::migrate::<BodyV1, PodCodec>(path, &v1, &v2, |body| {
+        Ok(BodyV2 {
+            position: body.position,
+            mass: body.position[0] * 2.0,
+        })
+    })
+    .unwrap();
+    let storage: Storage<u32, BodyV2> = Storage::open(path, v2).unwrap();
+    assert_eq!(
+        storage.get(3).await,
+        Some(BodyV2 {
+            position: [3.0; 3],
+            mass: 6.0
+        })
+    );
+    storage.close().await;
+}
+
+#[tokio::test]
+async fn constructors_without_a_schema_check_the_stored_one() {
+    use quadrax::cpu::storage::schema::{Schema, SchemaMismatch};
+
+    let tmp_dir = TempDir::new().unwrap();
+    let db_path = tmp_dir.path().join("typed.redb");
+    let path = db_path.to_str().unwrap();
+    let schema = Schema::named::<u32, u64>(3);
+    let storage: Storage<u32, u64> = Storage::open(path, schema.clone()).unwrap();
+    storage.insert(1, 10).await;
+    storage.close().await;
+
+    let storage: Storage<u32, u64> = Storage::new(path);
+    assert_eq!(storage.get(1).await, Some(10));
+    storage.close().await;
+    let storage = Storage::<u32, u64>::load_into_memory(path).unwrap();
+    assert_eq!(storage.get(1).await, Some(10));
+    storage.close().await;
+
+    let opened = std::panic::catch_unwind(|| Storage::<u32, [u32; 2]>::new(path));
+    assert!(opened.is_err());
+    let error = Storage::<u32, i64>::load_into_memory(path).err().unwrap();
+    let mismatch = error.downcast_ref::<SchemaMismatch>().unwrap();
+    assert_eq!(mismatch.stored, schema);
+    assert_eq!(mismatch.requested, Schema::named::<u32, i64>(3));
+    assert_eq!(Schema::read(path).unwrap(), Some(schema));
+}
+
+#[tokio::test]
+async fn storage_read_snapshot() {
+    use quadrax::cpu::storage::codec::{OrderedCodec, PodCodec};
+
+    let storage: Storage<u32, u64, OrderedCodec, PodCodec> = Storage::in_memory();
+    storage
+        .insert_batch((0..1000).map(|i| (i, i as u64)).collect())
+        .await;
+    let snapshot = storage.read_snapshot().await.unwrap();
+
+    storage.remove_range(0..500).await;
+    storage.insert(0, 42).await;
+    assert!(storage.compact().await.is_err());
+
+    let readers = (0..4)
+        .map(|t| {
+            let snapshot = snapshot.clone();
+            std::thread::spawn(move || {
+                (t * 250..(t + 1) * 250)
+                    .map(|i| snapshot.get(&i).unwrap().unwrap())
+                    .sum::<u64>()
+            })
+        })
+        .collect::<Vec<_>>();
+    let sum = readers.into_iter().map(|r| r.join().unwrap()).sum::<u64>();
+    assert_eq!(sum, (0..1000).sum::<u64>());
+    assert_eq!(snapshot.len().unwrap(), 1000);
+    assert_eq!(snapshot.range(10..12).unwrap(), vec![(10, 10), (11, 11)]);
+
+    drop(snapshot);
+    let latest = storage.read_snapshot().await.unwrap();
+    assert_eq!(latest.get(&0).unwrap(), Some(42));
+    assert_eq!(latest.get(&1).unwrap(), None);
+    assert_eq!(latest.len().unwrap(), 501);
+    drop(latest);
+    storage.close().await;
+}
@@ -0,0 +1,771 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+use quadrax::cpu::simulation::tecs::{
+    EntityID, TECS, World,
+    system::{Access, SystemSpec},
+};
+
+#[derive(Clone)]
+struct Position(f32);
+struct Velocity;
+struct Mass;
+
+/// Waits until `count` systems have arrived, or gives up after a second.
+fn rendezvous(arrived: &AtomicUsize, count: usize) -> bool {
+    arrived.fetch_add(1, Ordering::SeqCst);
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while Instant::now() < deadline {
+        if arrived.load(Ordering::SeqCst) >= count {
+            return true;
+        }
+        std::thread::yield_now();
+    }
+    false
+}
+
+#[test]
+fn disjoint_systems_run_in_parallel() {
+    let tecs = TECS::new();
+    tecs.create_entity((Position(0.0), Velocity, Mass));
+    let arrived = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+
+    let systems = ["position", "mass"].map(|name| {
+        let (arrived, tx) = (arrived.clone(), tx.clone());
+        let access = match name {
+            "position" => Access::default().write::<Position>().read::<Velocity>(),
+            _ => Access::default().write::<Mass>().read::<Velocity>(),
+        };
+        SystemSpec::shared(access, move |_: &World| {
+            tx.send((name, rendezvous(&arrived, 2))).unwrap();
+        })
+    });
+    assert!(!systems[0].access().conflicts(systems[1].access()));
+    tecs.add_systems(systems.into()).unwrap();
+    tecs.tick();
+
+    let mut met = [rx.recv().unwrap(), rx.recv().unwrap()];
+    met.sort_by_key(|(name, _)| *name);
+    assert_eq!(met, [("mass", true), ("position", true)]);
+}
+
+#[test]
+fn conflicting_systems_stay_serialised() {
+    let tecs = TECS::new();
+    tecs.create_entity((Position(1.0), Velocity));
+    let active = Arc::new(AtomicUsize::new(0));
+    let most_active = Arc::new(AtomicUsize::new(0));
+    let guarded = |f: fn(&mut Position)| {
+        let (active, most_active) = (active.clone(), most_active.clone());
+        SystemSpec::query::<&mut Position>(move |mut query| {
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            most_active.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            query.iter().for_each(f);
+            active.fetch_sub(1, Ordering::SeqCst);
+        })
+    };
+    let double = guarded(|p| p.0 *= 2.0);
+    let increment = guarded(|p| p.0 += 1.0);
+    let (tx, rx) = mpsc::channel();
+    let report = SystemSpec::query::<&Position>(move |mut query| {
+        for p in query.iter() {
+            tx.send(p.0).unwrap();
+        }
+    });
+    assert!(double.access().conflicts(report.access()));
+    tecs.add_systems(vec![double, increment, report]).unwrap();
+
+    for expected in [3.0, 7.0, 15.0] {
+        tecs.tick();
+        assert_eq!(rx.recv().unwrap(), expected);
+    }
+    assert_eq!(most_active.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn systems_follow_stages_and_constraints() {
+    use quadrax::cpu::simulation::tecs::schedule::Stage;
+    use std::sync::Mutex;
+
+    let tecs = TECS::new();
+    tecs.create_entity((Position(0.0), Velocity));
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let logged = |name: &'static str| {
+        let log = log.clone();
+        SystemSpec::query::<&Position>(move |_| log.lock().unwrap().push(name)).named(name)
+    };
+    tecs.add_systems(vec![
+        logged("export").in_stage(Stage::PostUpdate),
+        logged("integrate").after("forces"),
+    ])
+    .unwrap();
+    tecs.add_systems(vec![
+        logged("forces").after("input"),
+        logged("input").in_stage(Stage::PreUpdate),
+    ])
+    .unwrap();
+
+    let cycle = tecs.add_systems(vec![
+        logged("a").before("b"),
+        logged("b").before("c"),
+        logged("c").before("a").after("integrate"),
+    ]);
+    assert_eq!(
+        cycle.unwrap_err().to_string(),
+        "Cyclic ordering constraints between systems: a, b, c."
+    );
+    let backwards = tecs.add_systems(vec![logged("late").before("input")]);
+    assert_eq!(
+        backwards.unwrap_err().to_string(),
+        "System late in Update cannot run before system input in PreUpdate."
+    );
+
+    tecs.tick();
+    tecs.create_entity(());
+    assert_eq!(
+        *log.lock().unwrap(),
+        ["input", "forces", "integrate", "export"]
+    );
+}
+
+#[test]
+fn clock_and_run_modes() {
+    use quadrax::cpu::simulation::tecs::clock::Pacing;
+
+    let tecs = TECS::with_timestep(0.5);
+    let (tx, rx) = mpsc::channel();
+    tecs.add_systems(vec![move |world: &mut World| {
+        let time = world.time();
+        tx.send((time.tick(), time.elapsed(), time.dt())).unwrap();
+    }])
+    .unwrap();
+    let next = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    tecs.run_for(3);
+    assert_eq!(
+        [next(), next(), next()],
+        [(0, 0.0, 0.5), (1, 0.5, 0.5), (2, 1.0, 0.5)]
+    );
+    tecs.run_until(|world| world.time().tick() == 6);
+    assert_eq!([next().0, next().0, next().0], [3, 4, 5]);
+    tecs.step();
+    assert_eq!(next().0, 6);
+
+    tecs.run();
+    let running = next().0;
+    assert!(next().0 > running);
+    tecs.pause();
+    tecs.create_entity(());
+    let paused = rx.try_iter().last().map_or(running, |t| t.0);
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(rx.try_recv().is_err());
+    tecs.resume();
+    let resumed = next().0;
+    assert_eq!(resumed, paused + 1);
+
+    tecs.pause();
+    tecs.create_entity(());
+    let paused = rx.try_iter().last().map_or(resumed, |t| t.0);
+    for time_scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let error = tecs
+            .set_pacing(Pacing::RealTime { time_scale })
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Time scale must be positive and finite, found {time_scale}.")
+        );
+    }
+    tecs.set_pacing(Pacing::RealTime { time_scale: 10.0 })
+        .unwrap();
+    let start = Instant::now();
+    tecs.run_for(4);
+    let ticks = [next().0, next().0, next().0, next().0];
+    assert_eq!(ticks, [1, 2, 3, 4].map(|i| paused + i));
+    assert!(start.elapsed() >= Duration::from_millis(140));
+    tecs.create_entity(());
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn systems_share_resources() {
+    struct Gravity(f32);
+    #[derive(Default)]
+    struct Steps(u32);
+
+    let tecs = TECS::new();
+    tecs.create_entity((Position(10.0),));
+    tecs.insert_resource(Gravity(-2.0));
+    let fall = SystemSpec::shared(
+        Access::of::<&mut Position>().read::<Gravity>(),
+        |world: &World| {
+            let gravity = world.resource::<Gravity>().unwrap();
+            for p in world.query::<&mut Position>().iter() {
+                p.0 += gravity.0;
+            }
+        },
+    );
+    let count = SystemSpec::shared(Access::default().write::<Steps>(), |world: &World| {
+        if let Some(mut steps) = world.resource_mut::<Steps>() {
+            steps.0 += 1;
+        }
+    });
+    assert!(!fall.access().conflicts(count.access()));
+    let (tx, rx) = mpsc::channel();
+    let report = move |world: &mut World| {
+        let steps = world.resource::<Steps>().map(|s| s.0);
+        let position = world.query::<&Position>().iter().next().unwrap().0;
+        tx.send((steps, position)).unwrap();
+        if steps == Some(2) {
+            assert_eq!(world.remove_resource::<Steps>().unwrap().0, 2);
+            assert!(world.insert_resource(Gravity(1.0)).is_some());
+        }
+    };
+    tecs.add_systems(vec![fall, count]).unwrap();
+    tecs.add_systems(vec![report]).unwrap();
+
+    tecs.insert_resource(Steps::default());
+    tecs.run_for(4);
+    let recv = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(
+        [recv(), recv(), recv(), recv()],
+        [(Some(1), 8.0), (Some(2), 6.0), (None, 7.0), (None, 8.0)]
+    );
+}
+
+#[test]
+fn events_reach_readers_and_expire() {
+    use quadrax::cpu::simulation::tecs::{event::EventReader, schedule::Stage};
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Crossed(u64);
+
+    let tecs = TECS::new();
+    tecs.add_event::<Crossed>();
+    let (tx, rx) = mpsc::channel();
+    let eager_tx = tx.clone();
+    let mut eager = EventReader::<Crossed>::new();
+    let mut lazy = EventReader::<Crossed>::new();
+    tecs.add_systems(vec![
+        SystemSpec::shared(
+            Access::default().read_events::<Crossed>(),
+            move |world: &World| eager_tx.send(("eager", eager.read(world))).unwrap(),
+        ),
+        SystemSpec::shared(
+            Access::default().send_event::<Crossed>(),
+            |world: &World| world.send_event(Crossed(world.time().tick())),
+        )
+        .named("detect")
+        .in_stage(Stage::PreUpdate),
+    ])
+    .unwrap();
+    tecs.add_systems(vec![
+        SystemSpec::exclusive(move |world: &mut World| {
+            if world.time().tick() == 3 {
+                tx.send(("lazy", lazy.read(world))).unwrap();
+            }
+        })
+        .in_stage(Stage::PostUpdate),
+    ])
+    .unwrap();
+
+    for _ in 0..4 {
+        tecs.step();
+    }
+    assert_eq!(tecs.drain_events::<Crossed>(), [Crossed(3)]);
+    let received = rx.try_iter().collect::<Vec<_>>();
+    assert_eq!(
+        received,
+        [
+            ("eager", vec![Crossed(0)]),
+            ("eager", vec![Crossed(1)]),
+            ("eager", vec![Crossed(2)]),
+            ("eager", vec![Crossed(3)]),
+            ("lazy", vec![Crossed(2), Crossed(3)]),
+        ]
+    );
+    tecs.step();
+    assert_eq!(tecs.drain_events::<Crossed>(), [Crossed(4)]);
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [("eager", vec![Crossed(4)])]
+    );
+}
+
+#[test]
+fn change_filters_follow_each_systems_last_run() {
+    use quadrax::cpu::simulation::tecs::change::{Added, Changed};
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Health(u32);
+
+    fn changed(world: &World) -> Vec<EntityID> {
+        let mut changed = world.query_filtered::<EntityID, Changed<Health>>();
+        let mut changed = changed.iter().collect::<Vec<_>>();
+        changed.sort_by_key(|entity| entity.to_bits());
+        changed
+    }
+
+    let tecs = TECS::new();
+    tecs.track::<Health>();
+    let first = tecs.create_entity((Health(10),));
+    let second = tecs.create_entity((Health(20),));
+    let (changed_tx, changed_rx) = mpsc::channel();
+    let (observed_tx, observed_rx) = mpsc::channel();
+    let early_tx = changed_tx.clone();
+    tecs.add_systems(vec![
+        SystemSpec::shared(Access::default().read::<Health>(), move |world: &World| {
+            early_tx.send(("early", changed(world))).unwrap();
+        })
+        .named("early"),
+        SystemSpec::exclusive(move |world: &mut World| {
+            changed_tx.send(("damage", changed(world))).unwrap();
+            match world.time().tick() {
+                1 => {
+                    world.insert(first, (Health(5),)).unwrap();
+                    world.insert(second, (Health(20),)).unwrap();
+                }
+                2 => {
+                    world.remove::<(Health,)>(second).unwrap();
+                }
+                3 => {
+                    world.spawn((Health(1),));
+                }
+                _ => {}
+            }
+        })
+        .named("damage")
+        .after("early"),
+        SystemSpec::exclusive(move |world: &mut World| {
+            let mut added = world.query_filtered::<(EntityID, &Health), Added<Health>>();
+            let added = added.iter().map(|(_, health)| health.0).collect::<Vec<_>>();
+            let removed = world.removed::<Health>().iter().collect::<Vec<_>>();
+            observed_tx
+                .send((changed(world).len(), added, removed))
+                .unwrap();
+        })
+        .after("damage"),
+    ])
+    .unwrap();
+    tecs.tick_n(4).wait().unwrap();
+
+    // Systems see what changed since they last ran, but not their own changes.
+    assert_eq!(
+        changed_rx.try_iter().collect::<Vec<_>>(),
+        [
+            ("early", vec![first, second]),
+            ("damage", vec![first, second]),
+            ("early", vec![]),
+            ("damage", vec![]),
+            ("early", vec![first]),
+            ("damage", vec![]),
+            ("early", vec![]),
+            ("damage", vec![]),
+        ]
+    );
+    assert_eq!(
+        observed_rx.try_iter().collect::<Vec<_>>(),
+        [
+            (2, vec![10, 20], vec![]),
+            (1, vec![], vec![]),
+            (0, vec![], vec![second]),
+            (1, vec![1], vec![]),
+        ]
+    );
+
+    // Outside systems, everything since tracking began counts.
+    let (changed, removed) = tecs.with_world(|world| {
+        let removed = world.removed::<Health>().iter().count();
+        (changed(world).len(), removed)
+    });
+    assert_eq!((changed, removed), (2, 1));
+    tecs.step().wait().unwrap();
+    assert_eq!(
+        tecs.with_world(|world| world.removed::<Health>().iter().count()),
+        1
+    );
+    tecs.step().wait().unwrap();
+    assert_eq!(
+        tecs.with_world(|world| world.removed::<Health>().iter().count()),
+        0
+    );
+}
+
+#[test]
+fn change_detection_skips_batches_that_cannot_write() {
+    use quadrax::cpu::simulation::tecs::change::Changed;
+
+    static COMPARISONS: AtomicUsize = AtomicUsize::new(0);
+    #[derive(Clone)]
+    struct Health(u32);
+    impl PartialEq for Health {
+        fn eq(&self, other: &Self) -> bool {
+            COMPARISONS.fetch_add(1, Ordering::SeqCst);
+            self.0 == other.0
+        }
+    }
+
+    let tecs = TECS::new();
+    tecs.track::<Health>();
+    tecs.create_entity((Health(1), Position(0.0)));
+    let (tx, rx) = mpsc::channel();
+    let movers = (0..3).map(|i| {
+        SystemSpec::shared(Access::default().write::<Position>(), |world: &World| {
+            for position in world.query::<&mut Position>().iter() {
+                position.0 += 1.0;
+            }
+        })
+        .named(format!("move {i}"))
+    });
+    let watcher = SystemSpec::shared(Access::default().read::<Health>(), move |world: &World| {
+        let mut changed = world.query_filtered::<&Health, Changed<Health>>();
+        tx.send(changed.iter().count()).unwrap();
+    });
+    tecs.add_systems(movers.chain([watcher]).collect()).unwrap();
+    tecs.tick_n(3).wait().unwrap();
+
+    // Nothing in the schedule writes health, so it is only compared when each tick starts.
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), [1, 0, 0]);
+    assert_eq!(COMPARISONS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn change_filters_survive_system_panics() {
+    use quadrax::cpu::simulation::tecs::{change::Changed, fault::OnPanic};
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Health(u32);
+
+    fn changed(world: &World) -> usize {
+        world
+            .query_filtered::<EntityID, Changed<Health>>()
+            .iter()
+            .count()
+    }
+
+    let tecs = TECS::new();
+    tecs.track::<Health>();
+    tecs.create_entity((Health(10),));
+    let (tx, rx) = mpsc::channel();
+    tecs.add_systems(vec![
+        SystemSpec::shared(Access::default(), |world: &World| {
+            if world.time().tick() == 1 {
+                panic!("Lost track");
+            }
+        })
+        .on_panic(OnPanic::Disable),
+        SystemSpec::shared(Access::default().read::<Health>(), move |world: &World| {
+            tx.send(changed(world)).unwrap();
+        }),
+    ])
+    .unwrap();
+    tecs.tick().wait().unwrap();
+    assert!(tecs.tick().wait().is_err());
+    tecs.tick().wait().unwrap();
+
+    // The later system keeps its own baseline, and outside systems still see every change.
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), [1, 0, 0]);
+    assert_eq!(tecs.with_world(|world| changed(world)), 1);
+}
+
+#[test]
+fn commands_apply_at_sync_points() {
+    struct Fragment;
+
+    let tecs = TECS::new();
+    let parent = tecs.create_entity((Position(2.0), Velocity));
+    tecs.create_entity((Position(-1.0),));
+    let (tx, rx) = mpsc::channel();
+
+    let edit = SystemSpec::shared(
+        Access::of::<(EntityID, &Position, Option<&Velocity>)>(),
+        move |world: &World| {
+            let commands = world.commands();
+            let mut query = world.query::<(EntityID, &Position, Option<&Velocity>)>();
+            for (entity, position, velocity) in query.iter() {
+                if position.0 < 0.0 {
+                    commands.despawn(entity);
+                } else if velocity.is_some() {
+                    let child = commands.spawn((Position(position.0 * 2.0), Fragment));
+                    commands.insert(child, (Velocity,));
+                    commands.remove::<(Velocity,)>(entity);
+                    tx.send(child).unwrap();
+                }
+            }
+        },
+    );
+    let (seen_tx, seen_rx) = mpsc::channel();
+    let report = move |world: &mut World| {
+        let mut query =
+            world.query::<(EntityID, &Position, Option<&Velocity>, Option<&Fragment>)>();
+        let mut seen = query
+            .iter()
+            .map(|(e, p, v, f)| (e, p.0, v.is_some(), f.is_some()))
+            .collect::<Vec<_>>();
+        seen.sort_by(|a, b| a.1.total_cmp(&b.1));
+        seen_tx.send(seen).unwrap();
+    };
+    tecs.add_systems(vec![edit]).unwrap();
+    tecs.add_systems(vec![report]).unwrap();
+    tecs.step();
+
+    let child = rx.recv().unwrap();
+    assert_eq!(
+        seen_rx.recv().unwrap(),
+        [(parent, 2.0, false, false), (child, 4.0, true, true)]
+    );
+    tecs.step();
+    let grandchild = rx.recv().unwrap();
+    assert_eq!(seen_rx.recv().unwrap()[2], (grandchild, 8.0, true, true));
+}
+
+#[test]
+fn commands_after_a_system_panic_apply_last() {
+    use quadrax::cpu::simulation::tecs::fault::OnPanic;
+
+    let tecs = TECS::new();
+    let entity = tecs.create_entity((Position(0.0),));
+    tecs.add_systems(vec![
+        SystemSpec::shared(Access::default(), |_: &World| panic!("Lost its place"))
+            .on_panic(OnPanic::Disable),
+        SystemSpec::shared(Access::default(), move |world: &World| {
+            if world.time().tick() == 1 {
+                world.commands().insert(entity, (Position(1.0),));
+            }
+        }),
+    ])
+    .unwrap();
+    assert!(tecs.tick().wait().is_err());
+
+    // Recorded outside systems, so applied after the system's own commands.
+    tecs.with_world(move |world| world.commands().insert(entity, (Position(2.0),)));
+    tecs.tick().wait().unwrap();
+    assert_eq!(tecs.fetch::<(Position,)>(entity).unwrap().0.0, 2.0);
+}
+
+#[test]
+fn handle_edits_and_inspects_entities() {
+    #[derive(Clone, PartialEq, Debug)]
+    struct Speed(f32);
+    #[derive(Clone, PartialEq, Debug)]
+    struct Label(&'static str);
+
+    let tecs = TECS::new();
+    let entity = tecs.create_entity((Label("probe"),));
+    tecs.add_systems(vec![SystemSpec::query::<(&mut Label, &Speed)>(
+        |mut query| {
+            for (label, speed) in query.iter() {
+                if speed.0 > 1.0 {
+                    label.0 = "fast";
+                }
+            }
+        },
+    )])
+    .unwrap();
+
+    assert!(tecs.contains(entity));
+    assert_eq!(tecs.fetch::<(Label, Speed)>(entity), None);
+    tecs.insert_components(entity, (Speed(2.0),)).unwrap();
+    tecs.step();
+    assert_eq!(
+        tecs.fetch::<(Label, Speed)>(entity),
+        Some((Label("fast"), Speed(2.0)))
+    );
+    assert_eq!(
+        tecs.remove_components::<(Speed,)>(entity).unwrap(),
+        (Speed(2.0),)
+    );
+    assert!(tecs.remove_components::<(Speed,)>(entity).is_err());
+
+    let count = tecs.with_world(|world| world.query::<&Label>().iter().count());
+    assert_eq!(count, 1);
+    tecs.remove_entity(entity);
+    assert!(!tecs.contains(entity));
+    assert!(tecs.insert_components(entity, (Speed(1.0),)).is_err());
+}
+
+#[tokio::test]
+async fn ticks_complete_and_apply_back_pressure() {
+    use quadrax::cpu::simulation::tecs::Settings;
+
+    let tecs = TECS::with_settings(Settings {
+        queue_capacity: 2,
+        ..Settings::default()
+    });
+    let entity = tecs.create_entity((Position(0.0),));
+    tecs.add_systems(vec![SystemSpec::query::<&mut Position>(|mut query| {
+        std::thread::sleep(Duration::from_millis(20));
+        for p in query.iter() {
+            p.0 += 1.0;
+        }
+    })])
+    .unwrap();
+
+    assert_eq!(tecs.tick().await.unwrap(), 1);
+    assert_eq!(tecs.tick_n(3).await.unwrap(), 4);
+    assert_eq!(tecs.fetch::<(Position,)>(entity).unwrap().0.0, 4.0);
+
+    let start = Instant::now();
+    let handles = (0..6).map(|_| tecs.tick()).collect::<Vec<_>>();
+    // Only two messages fit in the queue, so queueing waits for most of the ticks to run.
+    assert!(start.elapsed() >= Duration::from_millis(60));
+    tecs.sync();
+    assert_eq!(tecs.fetch::<(Position,)>(entity).unwrap().0.0, 10.0);
+    let mut completed = Vec::new();
+    for handle in handles {
+        completed.push(handle.await.unwrap());
+    }
+    assert_eq!(completed, [5, 6, 7, 8, 9, 10]);
+    assert_eq!(
+        tokio::task::spawn_blocking(move || tecs.tick().wait())
+            .await
+            .unwrap()
+            .unwrap(),
+        11
+    );
+}
+
+#[test]
+fn default_names_are_unique_and_never_constraint_targets() {
+    use quadrax::cpu::simulation::tecs::{Settings, fault::OnPanic};
+
+    let unnamed = || SystemSpec::shared(Access::default(), |_: &World| panic!("unnamed"));
+    let default_name = unnamed().name().to_string();
+    let tecs = TECS::with_settings(Settings {
+        on_panic: OnPanic::Disable,
+        ..Settings::default()
+    });
+    // Matching the shared type name would order "late" both before and after the first system.
+    tecs.add_systems(vec![
+        unnamed().after("late"),
+        unnamed(),
+        SystemSpec::shared(Access::default(), |_: &World| {})
+            .named("late")
+            .after(default_name.clone()),
+    ])
+    .unwrap();
+    assert!(tecs.tick().wait().is_err());
+    let mut faulty = tecs
+        .faults()
+        .into_iter()
+        .map(|fault| fault.system)
+        .collect::<Vec<_>>();
+    faulty.sort();
+    assert_eq!(
+        faulty,
+        [format!("{default_name} #0"), format!("{default_name} #1")]
+    );
+}
+
+#[test]
+fn panics_are_reported_and_shutdown_joins() {
+    use quadrax::cpu::simulation::tecs::{
+        Settings,
+        fault::{OnPanic, SystemPanic},
+    };
+
+    fn faulty(world: &mut World) {
+        if world.time().tick() == 2 {
+            panic!("out of fuel");
+        }
+    }
+    let expected = SystemPanic {
+        system: "faulty".to_string(),
+        tick: 2,
+        message: "out of fuel".to_string(),
+    };
+
+    // Halting keeps the world as it was when the tick was abandoned.
+    let tecs = TECS::new();
+    let entity = tecs.create_entity((Position(0.0),));
+    tecs.add_systems(vec![
+        SystemSpec::exclusive(faulty).named("faulty"),
+        SystemSpec::query::<&mut Position>(|mut query| {
+            for p in query.iter() {
+                p.0 += 1.0;
+            }
+        })
+        .named("mover"),
+    ])
+    .unwrap();
+    assert_eq!(tecs.tick_n(2).wait().unwrap(), 2);
+    let error = tecs.tick_n(3).wait().unwrap_err();
+    assert_eq!(error.downcast_ref::<SystemPanic>(), Some(&expected));
+    assert_eq!(
+        error.to_string(),
+        "System faulty panicked at tick 2: out of fuel"
+    );
+    assert!(tecs.tick().wait().is_err());
+    assert_eq!(tecs.fetch::<(Position,)>(entity).unwrap().0.0, 2.0);
+    assert_eq!(tecs.faults(), std::slice::from_ref(&expected));
+    let error = tecs.shutdown().unwrap_err();
+    assert_eq!(error.downcast_ref::<SystemPanic>(), Some(&expected));
+
+    // Disabling drops the faulty system and carries on with the rest.
+    let tecs = TECS::with_settings(Settings {
+        on_panic: OnPanic::Disable,
+        ..Settings::default()
+    });
+    let entity = tecs.create_entity((Position(0.0),));
+    tecs.add_systems(vec![
+        SystemSpec::exclusive(faulty).named("faulty"),
+        SystemSpec::query::<&mut Position>(|mut query| {
+            for p in query.iter() {
+                p.0 += 1.0;
+            }
+        })
+        .named("mover"),
+    ])
+    .unwrap();
+    let error = tecs.tick_n(4).wait().unwrap_err();
+    assert_eq!(error.downcast_ref::<SystemPanic>(), Some(&expected));
+    assert_eq!(tecs.tick_n(2).wait().unwrap(), 6);
+    assert_eq!(tecs.fetch::<(Position,)>(entity).unwrap().0.0, 6.0);
+    assert_eq!(tecs.faults(), [expected]);
+    tecs.shutdown().unwrap();
+
+    // Shutting down handles everything queued first; dropping does too.
+    let (tx, rx) = mpsc::channel();
+    let tecs = TECS::new();
+    tecs.add_systems(vec![move |world: &mut World| {
+        tx.send(world.time().tick()).unwrap();
+    }])
+    .unwrap();
+    tecs.tick_n(5);
+    tecs.shutdown().unwrap();
+    assert_eq!(rx.try_iter().count(), 5);
+
+    let tecs = TECS::new();
+    let (tx, rx) = mpsc::channel();
+    tecs.add_systems(vec![move |world: &mut World| {
+        tx.send(world.time().tick()).unwrap();
+    }])
+    .unwrap();
+    tecs.tick_n(3);
+    drop(tecs);
+    assert_eq!(rx.try_iter().count(), 3);
+}
+
+#[test]
+fn panics_outside_systems_stop_the_thread() {
+    let tecs = TECS::new();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        tecs.with_world(|_| -> u32 { panic!("bad edit") })
+    }));
+    let message = result.unwrap_err();
+    assert_eq!(
+        message.downcast_ref::<String>().unwrap(),
+        "Simulation thread stopped: bad edit"
+    );
+    assert_eq!(
+        tecs.shutdown().unwrap_err().to_string(),
+        "Simulation thread panicked: bad edit"
+    );
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::identity_op)]
+
 use quadrax::gpu::{
     backend::Backend,
     texture::{Texture, TextureRole},